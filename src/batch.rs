@@ -0,0 +1,305 @@
+use crate::executor::{EventSink, ExecutionEvent, RunRecord, RunStatus};
+use crate::report::Summary;
+use crate::pipeline::{self, Prepared};
+use crate::{CommandSpec, Config, Executor, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The outcome of running one file as part of a batch.
+#[derive(Debug)]
+pub struct FileRun {
+    pub file: String,
+    /// The resolved command, or `None` if the file was rejected before running.
+    pub cmd_spec: Option<CommandSpec>,
+    /// Problems found with the file that didn't stop it from running.
+    pub warnings: Vec<String>,
+    pub record: RunRecord,
+}
+
+impl FileRun {
+    pub fn status(&self) -> RunStatus {
+        self.record.status()
+    }
+}
+
+impl Serialize for FileRun {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct View<'a> {
+            file: &'a str,
+            language: Option<&'a str>,
+            command: Option<&'a CommandSpec>,
+            #[serde(skip_serializing_if = "<[String]>::is_empty")]
+            warnings: &'a [String],
+            #[serde(flatten)]
+            record: &'a RunRecord,
+        }
+
+        View {
+            file: &self.file,
+            language: self.cmd_spec.as_ref().map(|spec| spec.language.as_str()),
+            command: self.cmd_spec.as_ref(),
+            warnings: &self.warnings,
+            record: &self.record,
+        }
+        .serialize(serializer)
+    }
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Expands glob patterns into sorted file lists. Other arguments, and patterns
+/// that match nothing, are kept as-is so they fail validation with a clear error.
+pub fn expand_patterns(patterns: &[String]) -> Vec<String> {
+    let mut files = Vec::new();
+
+    for pattern in patterns {
+        if !is_glob(pattern) {
+            files.push(pattern.clone());
+            continue;
+        }
+
+        let mut matches: Vec<String> = glob::glob(pattern)
+            .map(|paths| {
+                paths
+                    .filter_map(|path| path.ok())
+                    .filter(|path| path.is_file())
+                    .map(|path| path.display().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if matches.is_empty() {
+            files.push(pattern.clone());
+        } else {
+            matches.sort();
+            files.append(&mut matches);
+        }
+    }
+
+    files
+}
+
+/// Runs `files` with at most `jobs` running at once, each with the config
+/// `load` returns for it, so files from different projects get their own
+/// `.code-runner.toml`. `on_done` is called as each file finishes, one call at
+/// a time, so output from different files is never interleaved. Results are
+/// returned in the order of `files`.
+pub fn run<L, F>(files: &[String], load: L, jobs: usize, on_done: F) -> Vec<FileRun>
+where
+    L: Fn(&str) -> Result<Config> + Sync,
+    F: Fn(&FileRun) + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let slots: Mutex<Vec<Option<FileRun>>> = Mutex::new((0..files.len()).map(|_| None).collect());
+    let report_lock = Mutex::new(());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(file) = files.get(index) else {
+                    break;
+                };
+
+                let file_run = match load(file) {
+                    Ok(config) => run_with(background_executor(&config), file, &config),
+                    Err(e) => FileRun {
+                        file: file.to_string(),
+                        cmd_spec: None,
+                        warnings: Vec::new(),
+                        record: RunRecord::failed(e),
+                    },
+                };
+                {
+                    let _guard = report_lock.lock().unwrap_or_else(|e| e.into_inner());
+                    on_done(&file_run);
+                }
+                slots.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(file_run);
+            });
+        }
+    });
+
+    slots
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Prepares, runs and cleans up one file, capturing its output rather than
+/// printing it. `events` also receives a `RunStarted` event once the command
+/// is resolved.
+pub fn run_file(
+    file: &str,
+    config: &Config,
+    cancel: Option<Arc<AtomicBool>>,
+    events: Option<EventSink>,
+) -> FileRun {
+    let mut executor = quiet_executor(config);
+    if let Some(cancel) = cancel {
+        executor = executor.with_cancel(cancel);
+    }
+    if let Some(events) = events {
+        executor = executor.with_events(events);
+    }
+    run_with(executor, file, config)
+}
+
+fn quiet_executor(config: &Config) -> Executor {
+    Executor::new(Config {
+        silent_mode: true,
+        ..config.clone()
+    })
+}
+
+/// Batch jobs run side by side, so none of them may take the terminal.
+fn background_executor(config: &Config) -> Executor {
+    quiet_executor(config).in_background()
+}
+
+fn run_with(executor: Executor, file: &str, config: &Config) -> FileRun {
+    match pipeline::prepare(file, config) {
+        Ok(Prepared { cmd_spec, warnings, .. }) => {
+            executor.emit(ExecutionEvent::RunStarted { command: cmd_spec.clone() });
+            let record = executor.record(&cmd_spec);
+            if let Err(e) = executor.cleanup(&cmd_spec) {
+                log::warn!("Failed to clean up after {}: {}", file, e);
+            }
+            FileRun {
+                file: file.to_string(),
+                cmd_spec: Some(cmd_spec),
+                warnings,
+                record,
+            }
+        }
+        Err(e) => FileRun {
+            file: file.to_string(),
+            cmd_spec: None,
+            warnings: Vec::new(),
+            record: RunRecord::failed(e),
+        },
+    }
+}
+
+/// The captured output of one file, framed with its name.
+pub fn render_file(file_run: &FileRun) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "==> {} <==", file_run.file);
+    for warning in &file_run.warnings {
+        let _ = writeln!(out, "Warning: {}", warning);
+    }
+
+    if let Some(cmd_spec) = &file_run.cmd_spec {
+        let _ = writeln!(out, "Running: {}\n", cmd_spec.display_string());
+    }
+
+    for result in &file_run.record.results {
+        if !result.stderr.trim().is_empty() {
+            let _ = writeln!(out, "stderr:\n{}", result.stderr);
+        }
+        out.push_str(&result.stdout);
+        if !result.stdout.is_empty() && !result.stdout.ends_with('\n') {
+            out.push('\n');
+        }
+        for diagnostic in &result.diagnostics {
+            let _ = writeln!(out, "  {}", diagnostic);
+        }
+    }
+
+    if let Some(error) = &file_run.record.error {
+        let _ = writeln!(out, "Error: {}", error);
+    }
+
+    out
+}
+
+pub fn render_summary(runs: &[FileRun]) -> String {
+    let mut out = String::from("\nSummary\n");
+    let _ = writeln!(out, "  {:<14} {:>10}  FILE", "STATUS", "TIME");
+
+    for file_run in runs {
+        let _ = writeln!(
+            out,
+            "  {:<14} {:>10}  {}",
+            file_run.status().label(),
+            format!("{:.2} s", file_run.record.wall_time().as_secs_f64()),
+            file_run.file,
+        );
+    }
+
+    let summary = Summary::of(runs);
+    let _ = writeln!(
+        out,
+        "\n{} files: {} succeeded, {} failed, {} timed out",
+        summary.total, summary.passed, summary.failed, summary.timed_out,
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_expand_patterns() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        for name in ["b.py", "a.py", "nested/c.py", "notes.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let pattern = format!("{}/**/*.py", dir.path().display());
+        let files = expand_patterns(&[pattern, "literal.go".to_string()]);
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| f.trim_start_matches(&dir.path().display().to_string()).to_string())
+            .collect();
+        assert_eq!(names, ["/a.py", "/b.py", "/nested/c.py", "literal.go"]);
+    }
+
+    #[test]
+    fn test_unmatched_glob_is_kept() {
+        assert_eq!(expand_patterns(&["nothing/*.zz".to_string()]), ["nothing/*.zz"]);
+    }
+
+    #[test]
+    fn test_run_keeps_input_order() {
+        let files = vec!["missing_one.py".to_string(), "missing_two.py".to_string()];
+        let runs = run(&files, |_| Ok(Config::default()), 4, |_| {});
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].file, "missing_one.py");
+        assert!(runs.iter().all(|run| run.status() == RunStatus::Failed));
+        assert!(render_summary(&runs).contains("2 files: 0 succeeded, 2 failed"));
+    }
+
+    #[test]
+    fn test_warnings_are_kept_with_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("bom.py");
+        std::fs::write(&file, "\u{FEFF}print(1)\n").unwrap();
+
+        let file_run = run_file(file.to_str().unwrap(), &Config::default(), None, None);
+        assert_eq!(file_run.warnings.len(), 1);
+        assert!(render_file(&file_run).contains("\nWarning: "), "{}", render_file(&file_run));
+    }
+
+    #[test]
+    fn test_config_errors_fail_only_their_file() {
+        let files = vec!["a.py".to_string(), "b.py".to_string()];
+        let runs = run(&files, |file| match file {
+            "a.py" => Err(crate::CodeRunnerError::ConfigError("broken".to_string())),
+            _ => Ok(Config::default()),
+        }, 2, |_| {});
+        assert_eq!(runs[0].record.error.as_ref().unwrap().to_string(), "Configuration error: broken");
+        assert!(!runs[1].record.error.as_ref().unwrap().to_string().contains("broken"));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{CodeRunnerError, CommandSpec, Config, Executor, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+pub struct BenchOptions {
+    pub runs: usize,
+    pub warmup: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self { runs: 10, warmup: 0 }
+    }
+}
+
+/// Measurements from one run of the benchmarked program.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sample {
+    pub wall_ms: f64,
+    pub cpu_ms: Option<f64>,
+    pub peak_memory_kb: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub min: f64,
+    pub median: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+        // Sample standard deviation; a single run has none.
+        let stddev = if n > 1 {
+            (sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            min: sorted[0],
+            median,
+            mean,
+            stddev,
+            max: sorted[n - 1],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub command: String,
+    pub runs: usize,
+    pub warmup: usize,
+    pub wall_ms: Stats,
+    pub cpu_ms: Option<Stats>,
+    pub peak_memory_kb: Option<u64>,
+    pub samples: Vec<Sample>,
+}
+
+/// Compiles `cmd_spec` once, then times `options.runs` executions of its run
+/// stage after `options.warmup` untimed ones. Program output is discarded.
+pub fn run(config: &Config, cmd_spec: &CommandSpec, options: &BenchOptions) -> Result<BenchReport> {
+    if options.runs == 0 {
+        return Err(CodeRunnerError::ConfigError("Benchmark needs at least one run".to_string()));
+    }
+
+    let executor = Executor::new(Config {
+        silent_mode: true,
+        ..config.clone()
+    });
+
+    executor.compile(cmd_spec)?;
+
+    for _ in 0..options.warmup {
+        executor.run(cmd_spec)?;
+    }
+
+    let mut samples = Vec::with_capacity(options.runs);
+    for _ in 0..options.runs {
+        let result = executor.run(cmd_spec)?;
+        samples.push(Sample {
+            wall_ms: millis(result.wall_time),
+            cpu_ms: result.cpu_time.map(millis),
+            peak_memory_kb: result.peak_memory_kb,
+        });
+    }
+
+    let wall: Vec<f64> = samples.iter().map(|s| s.wall_ms).collect();
+    let cpu: Option<Vec<f64>> = samples.iter().map(|s| s.cpu_ms).collect();
+
+    Ok(BenchReport {
+        command: cmd_spec.run_stage().display_string(),
+        runs: options.runs,
+        warmup: options.warmup,
+        wall_ms: Stats::from_values(&wall).expect("at least one sample"),
+        cpu_ms: cpu.and_then(|cpu| Stats::from_values(&cpu)),
+        peak_memory_kb: samples.iter().filter_map(|s| s.peak_memory_kb).max(),
+        samples,
+    })
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl BenchReport {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| CodeRunnerError::ExecutionFailed(format!("Cannot encode report: {}", e)))
+    }
+
+    /// One row per timed run.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("run,wall_ms,cpu_ms,peak_memory_kb\n");
+        for (i, sample) in self.samples.iter().enumerate() {
+            let _ = writeln!(
+                csv,
+                "{},{:.3},{},{}",
+                i + 1,
+                sample.wall_ms,
+                sample.cpu_ms.map(|v| format!("{:.3}", v)).unwrap_or_default(),
+                sample.peak_memory_kb.map(|v| v.to_string()).unwrap_or_default(),
+            );
+        }
+        csv
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Benchmark: {}", self.command);
+        let _ = writeln!(out, "  {} runs, {} warmup\n", self.runs, self.warmup);
+        let _ = writeln!(
+            out,
+            "  {:<6} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "", "min", "median", "mean", "stddev", "max"
+        );
+        render_row(&mut out, "wall", &self.wall_ms);
+        if let Some(cpu) = &self.cpu_ms {
+            render_row(&mut out, "cpu", cpu);
+        }
+        if let Some(peak) = self.peak_memory_kb {
+            let _ = writeln!(out, "\n  peak memory: {} KB", peak);
+        }
+        out
+    }
+}
+
+fn render_row(out: &mut String, label: &str, stats: &Stats) {
+    let _ = writeln!(
+        out,
+        "  {:<6} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        label,
+        format_ms(stats.min),
+        format_ms(stats.median),
+        format_ms(stats.mean),
+        format_ms(stats.stddev),
+        format_ms(stats.max),
+    );
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.3} s", ms / 1000.0)
+    } else {
+        format!("{:.2} ms", ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::from_values(&[4.0, 1.0, 3.0, 2.0]).unwrap();
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.median, 2.5);
+        assert_eq!(stats.mean, 2.5);
+        assert!((stats.stddev - 1.2910).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_single_value_has_no_spread() {
+        let stats = Stats::from_values(&[7.0]).unwrap();
+        assert_eq!(stats.median, 7.0);
+        assert_eq!(stats.stddev, 0.0);
+        assert!(Stats::from_values(&[]).is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_bench_runs_requested_times() {
+        let spec = CommandSpec::new("true".to_string(), vec![]);
+        let report = run(&Config::default(), &spec, &BenchOptions { runs: 3, warmup: 1 }).unwrap();
+        assert_eq!(report.samples.len(), 3);
+        assert!(report.to_csv().lines().count() == 4);
+        assert!(report.to_json().unwrap().contains("\"median\""));
+    }
+}
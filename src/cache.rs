@@ -0,0 +1,144 @@
+//! Build directories left in the temp dir by runs with cleanup turned off,
+//! or by runs that were killed before they could clean up.
+
+use crate::utils::BUILD_DIR_PREFIX;
+use crate::{process, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct BuildDir {
+    pub path: PathBuf,
+    /// The process that created the directory.
+    pub pid: u32,
+    pub bytes: u64,
+    /// Whether that process is still running, in which case the directory
+    /// is left alone.
+    pub in_use: bool,
+}
+
+/// Where build directories are created.
+pub fn root() -> PathBuf {
+    std::env::temp_dir()
+}
+
+/// Every build directory under [`root`].
+pub fn build_dirs() -> Result<Vec<BuildDir>> {
+    build_dirs_in(&root())
+}
+
+/// Removes the build directories no running process owns, returning them.
+pub fn clean() -> Result<Vec<BuildDir>> {
+    clean_in(&root())
+}
+
+fn build_dirs_in(root: &Path) -> Result<Vec<BuildDir>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        let Some(pid) = path.file_name().and_then(|name| name.to_str()).and_then(owner) else {
+            continue;
+        };
+        if path.is_dir() {
+            dirs.push(BuildDir { bytes: dir_size(&path), pid, in_use: process::is_alive(pid), path });
+        }
+    }
+    dirs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(dirs)
+}
+
+/// Other users' build directories in a shared temp dir are skipped, as are
+/// any that can't be removed.
+fn clean_in(root: &Path) -> Result<Vec<BuildDir>> {
+    let mut removed = Vec::new();
+    for dir in build_dirs_in(root)?.into_iter().filter(|dir| !dir.in_use && owned_by_us(&dir.path)) {
+        match fs::remove_dir_all(&dir.path) {
+            Ok(()) => removed.push(dir),
+            Err(e) => log::warn!("Cannot remove {}: {}", dir.path.display(), e),
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(unix)]
+fn owned_by_us(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::geteuid() };
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.uid() == uid)
+}
+
+#[cfg(not(unix))]
+fn owned_by_us(_path: &Path) -> bool {
+    true
+}
+
+/// The pid in a `code-runner-<pid>-<suffix>` name.
+fn owner(name: &str) -> Option<u32> {
+    let (pid, suffix) = name.strip_prefix(BUILD_DIR_PREFIX)?.split_once('-')?;
+    if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    pid.parse().ok()
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map_or(0, |metadata| metadata.len()),
+        })
+        .sum()
+}
+
+/// `bytes` in the largest unit that keeps it above one, e.g. `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_keeps_dirs_in_use() {
+        let root = tempfile::TempDir::new().unwrap();
+        let ours = root.path().join(format!("code-runner-{}-0", std::process::id()));
+        let stale = root.path().join("code-runner-4294967-a1B2c3");
+        fs::create_dir_all(&ours).unwrap();
+        fs::create_dir_all(&stale).unwrap();
+        fs::write(stale.join("main"), [0u8; 100]).unwrap();
+        fs::create_dir(root.path().join("code-runner-cache")).unwrap();
+        fs::create_dir(root.path().join("code-runner-12-not.ours")).unwrap();
+
+        let dirs = build_dirs_in(root.path()).unwrap();
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs.iter().find(|dir| dir.path == stale).unwrap().bytes, 100);
+
+        let removed = clean_in(root.path()).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].pid, 4294967);
+        assert!(ours.exists() && !stale.exists());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
@@ -1,12 +1,21 @@
-use std::path::Path;
+use crate::config::LanguageConfig;
+use crate::directives::Directives;
+use crate::install::Install;
+use crate::install::Installer::*;
+use crate::utils::BuildDirGuard;
+use crate::{diagnostics, CodeRunnerError, Config, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-#[derive(Clone, Debug)]
-pub struct CommandSpec {
+/// A single program invocation within a [`CommandSpec`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Stage {
     pub program: String,
     pub args: Vec<String>,
 }
 
-impl CommandSpec {
+impl Stage {
     pub fn new(program: String, args: Vec<String>) -> Self {
         Self { program, args }
     }
@@ -24,144 +33,702 @@ impl CommandSpec {
     }
 }
 
+/// How to run a source file: `program` with `args`, preceded by an optional
+/// `compile` stage for languages that build an artifact first.
+#[derive(Clone, Debug, Serialize)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub compile: Option<Stage>,
+    pub language: String,
+    pub cwd: Option<PathBuf>,
+    /// Private directory the compile stage writes its artifacts to.
+    pub build_dir: Option<PathBuf>,
+    /// Removes `build_dir` once the last copy of the spec is dropped.
+    #[serde(skip)]
+    pub build_guard: Option<Arc<BuildDirGuard>>,
+    /// File fed to the program's stdin.
+    pub stdin: Option<PathBuf>,
+    /// Timeout in seconds set by the file itself.
+    pub timeout: Option<u64>,
+}
+
+impl CommandSpec {
+    pub fn new(program: String, args: Vec<String>) -> Self {
+        Self {
+            program,
+            args,
+            compile: None,
+            language: String::new(),
+            cwd: None,
+            build_dir: None,
+            build_guard: None,
+            stdin: None,
+            timeout: None,
+        }
+    }
+    
+    pub fn compiled(compile: Stage, program: String, args: Vec<String>) -> Self {
+        Self { compile: Some(compile), ..Self::new(program, args) }
+    }
+    
+    pub fn run_stage(&self) -> Stage {
+        Stage::new(self.program.clone(), self.args.clone())
+    }
+    
+    /// Every stage in execution order.
+    pub fn stages(&self) -> Vec<Stage> {
+        self.compile.iter().cloned().chain(std::iter::once(self.run_stage())).collect()
+    }
+    
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = language.to_string();
+        self
+    }
+    
+    pub fn with_cwd(mut self, cwd: Option<PathBuf>) -> Self {
+        self.cwd = cwd;
+        self
+    }
+    
+    pub fn with_build_dir(mut self, build_dir: Arc<BuildDirGuard>) -> Self {
+        self.build_dir = Some(build_dir.path().to_path_buf());
+        self.build_guard = Some(build_dir);
+        self
+    }
+    
+    pub fn display_string(&self) -> String {
+        self.stages()
+            .iter()
+            .map(Stage::display_string)
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+}
+
+pub struct Language {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    /// Line comment marker, used to find `code-runner:` directives.
+    pub comment: &'static str,
+    /// Every program running a file needs, in the order they are used.
+    pub programs: &'static [Program],
+    /// Whether files are built before they run. The first program is then
+    /// the compiler, and a second one, if any, runs what it built (`java`).
+    pub compiled: bool,
+    /// Turns the language's compiler and runtime errors into diagnostics.
+    pub diagnostics: Option<diagnostics::Parser>,
+}
+
+/// A program a language needs, with the ways to install it.
+pub struct Program {
+    pub name: &'static str,
+    pub install: &'static [Install],
+}
+
+const NODE: Program = Program { name: "node", install: &[
+    Install(Nvm, "--lts"), Install(Apt, "nodejs"), Install(Dnf, "nodejs"),
+    Install(Pacman, "nodejs"), Install(Apk, "nodejs"), Install(Brew, "node"),
+    Install(Nix, "nodejs"),
+] };
+const NPX: Program = Program { name: "npx", install: &[
+    Install(Nvm, "--lts"), Install(Apt, "npm"), Install(Dnf, "npm"), Install(Pacman, "npm"),
+    Install(Apk, "npm"), Install(Brew, "node"), Install(Nix, "nodejs"),
+] };
+const PYTHON: Program = Program { name: "python", install: &[
+    Install(Pyenv, "3.12"), Install(Apt, "python-is-python3"), Install(Dnf, "python3"),
+    Install(Pacman, "python"), Install(Apk, "python3"), Install(Brew, "python"),
+    Install(Nix, "python3"),
+] };
+const GO: Program = Program { name: "go", install: &[
+    Install(Apt, "golang-go"), Install(Dnf, "golang"), Install(Pacman, "go"), Install(Apk, "go"),
+    Install(Brew, "go"), Install(Nix, "go"),
+] };
+const RUSTC: Program = Program { name: "rustc", install: &[
+    Install(Rustup, "default stable"), Install(Apt, "rustc"), Install(Dnf, "rust"),
+    Install(Pacman, "rust"), Install(Apk, "rust"), Install(Brew, "rust"), Install(Nix, "rustc"),
+] };
+const GCC: Program = Program { name: "gcc", install: &[
+    Install(Apt, "gcc"), Install(Dnf, "gcc"), Install(Pacman, "gcc"), Install(Apk, "gcc"),
+    Install(Brew, "gcc"), Install(Nix, "gcc"),
+] };
+const GXX: Program = Program { name: "g++", install: &[
+    Install(Apt, "g++"), Install(Dnf, "gcc-c++"), Install(Pacman, "gcc"), Install(Apk, "g++"),
+    Install(Brew, "gcc"), Install(Nix, "gcc"),
+] };
+const JAVAC: Program = Program { name: "javac", install: &[
+    Install(Sdkman, "java"), Install(Apt, "default-jdk"), Install(Dnf, "java-latest-openjdk-devel"),
+    Install(Pacman, "jdk-openjdk"), Install(Apk, "openjdk21"), Install(Brew, "openjdk"),
+    Install(Nix, "jdk"),
+] };
+const JAVA: Program = Program { name: "java", install: &[
+    Install(Sdkman, "java"), Install(Apt, "default-jdk"), Install(Dnf, "java-latest-openjdk-devel"),
+    Install(Pacman, "jdk-openjdk"), Install(Apk, "openjdk21"), Install(Brew, "openjdk"),
+    Install(Nix, "jdk"),
+] };
+const PHP: Program = Program { name: "php", install: &[
+    Install(Apt, "php-cli"), Install(Dnf, "php-cli"), Install(Pacman, "php"), Install(Apk, "php"),
+    Install(Brew, "php"), Install(Nix, "php"),
+] };
+const RUBY: Program = Program { name: "ruby", install: &[
+    Install(Apt, "ruby"), Install(Dnf, "ruby"), Install(Pacman, "ruby"), Install(Apk, "ruby"),
+    Install(Brew, "ruby"), Install(Nix, "ruby"),
+] };
+const BASH: Program = Program { name: "bash", install: &[
+    Install(Apt, "bash"), Install(Dnf, "bash"), Install(Pacman, "bash"), Install(Apk, "bash"),
+    Install(Brew, "bash"), Install(Nix, "bash"),
+] };
+const POWERSHELL: Program = Program { name: "powershell", install: &[
+    Install(Brew, "--cask powershell"), Install(Nix, "powershell"),
+] };
+const LUA: Program = Program { name: "lua", install: &[
+    Install(Apt, "lua5.4"), Install(Dnf, "lua"), Install(Pacman, "lua"), Install(Apk, "lua5.4"),
+    Install(Brew, "lua"), Install(Nix, "lua"),
+] };
+const PERL: Program = Program { name: "perl", install: &[
+    Install(Apt, "perl"), Install(Dnf, "perl"), Install(Pacman, "perl"), Install(Apk, "perl"),
+    Install(Brew, "perl"), Install(Nix, "perl"),
+] };
+const RSCRIPT: Program = Program { name: "Rscript", install: &[
+    Install(Apt, "r-base"), Install(Dnf, "R"), Install(Pacman, "r"), Install(Apk, "R"),
+    Install(Brew, "r"), Install(Nix, "R"),
+] };
+const SWIFT: Program = Program { name: "swift", install: &[Install(Nix, "swift")] };
+const DART: Program = Program { name: "dart", install: &[
+    Install(Pacman, "dart"), Install(Brew, "dart-lang/dart/dart"), Install(Nix, "dart"),
+] };
+const ZIG: Program = Program { name: "zig", install: &[
+    Install(Dnf, "zig"), Install(Pacman, "zig"), Install(Apk, "zig"), Install(Brew, "zig"),
+    Install(Nix, "zig"),
+] };
+const RUNHASKELL: Program = Program { name: "runhaskell", install: &[
+    Install(Apt, "ghc"), Install(Dnf, "ghc"), Install(Pacman, "ghc"), Install(Apk, "ghc"),
+    Install(Brew, "ghc"), Install(Nix, "ghc"),
+] };
+const JULIA: Program = Program { name: "julia", install: &[Install(Brew, "julia"), Install(Nix, "julia")] };
+const ELIXIR: Program = Program { name: "elixir", install: &[
+    Install(Apt, "elixir"), Install(Dnf, "elixir"), Install(Pacman, "elixir"),
+    Install(Apk, "elixir"), Install(Brew, "elixir"), Install(Nix, "elixir"),
+] };
+const CRYSTAL: Program = Program { name: "crystal", install: &[
+    Install(Pacman, "crystal"), Install(Apk, "crystal"), Install(Brew, "crystal"),
+    Install(Nix, "crystal"),
+] };
+const SCALA: Program = Program { name: "scala", install: &[
+    Install(Sdkman, "scala"), Install(Pacman, "scala"), Install(Brew, "scala"),
+    Install(Nix, "scala"),
+] };
+const GROOVY: Program = Program { name: "groovy", install: &[
+    Install(Sdkman, "groovy"), Install(Apt, "groovy"), Install(Pacman, "groovy"),
+    Install(Brew, "groovy"), Install(Nix, "groovy"),
+] };
+const CLOJURE: Program = Program { name: "clojure", install: &[
+    Install(Pacman, "clojure"), Install(Brew, "clojure/tools/clojure"), Install(Nix, "clojure"),
+] };
+const RACKET: Program = Program { name: "racket", install: &[
+    Install(Apt, "racket"), Install(Dnf, "racket"), Install(Pacman, "racket"),
+    Install(Brew, "--cask racket"), Install(Nix, "racket"),
+] };
+const OCAML: Program = Program { name: "ocaml", install: &[
+    Install(Apt, "ocaml"), Install(Dnf, "ocaml"), Install(Pacman, "ocaml"), Install(Apk, "ocaml"),
+    Install(Brew, "ocaml"), Install(Nix, "ocaml"),
+] };
+const ESCRIPT: Program = Program { name: "escript", install: &[
+    Install(Apt, "erlang"), Install(Dnf, "erlang"), Install(Pacman, "erlang"),
+    Install(Apk, "erlang"), Install(Brew, "erlang"), Install(Nix, "erlang"),
+] };
+const TCLSH: Program = Program { name: "tclsh", install: &[
+    Install(Apt, "tcl"), Install(Dnf, "tcl"), Install(Pacman, "tcl"), Install(Apk, "tcl"),
+    Install(Brew, "tcl-tk"), Install(Nix, "tcl"),
+] };
+const KOTLINC: Program = Program { name: "kotlinc", install: &[
+    Install(Sdkman, "kotlin"), Install(Pacman, "kotlin"), Install(Brew, "kotlin"),
+    Install(Nix, "kotlin"),
+] };
+const NIM: Program = Program { name: "nim", install: &[
+    Install(Apt, "nim"), Install(Pacman, "nim"), Install(Apk, "nim"), Install(Brew, "nim"),
+    Install(Nix, "nim"),
+] };
+const DMD: Program = Program { name: "dmd", install: &[
+    Install(Pacman, "dmd"), Install(Brew, "dmd"), Install(Nix, "dmd"),
+] };
+const DOTNET: Program = Program { name: "dotnet", install: &[
+    Install(Apt, "dotnet-sdk-8.0"), Install(Dnf, "dotnet-sdk-8.0"), Install(Pacman, "dotnet-sdk"),
+    Install(Apk, "dotnet8-sdk"), Install(Brew, "--cask dotnet-sdk"), Install(Nix, "dotnet-sdk"),
+] };
+
+pub const LANGUAGES: &[Language] = &[
+    Language { name: "javascript", extensions: &["js"], comment: "//", programs: &[NODE], compiled: false, diagnostics: Some(diagnostics::node) },
+    Language { name: "typescript", extensions: &["ts"], comment: "//", programs: &[NPX, NODE], compiled: false, diagnostics: Some(diagnostics::tsc) },
+    Language { name: "python", extensions: &["py"], comment: "#", programs: &[PYTHON], compiled: false, diagnostics: Some(diagnostics::python) },
+    Language { name: "go", extensions: &["go"], comment: "//", programs: &[GO], compiled: true, diagnostics: Some(diagnostics::go) },
+    Language { name: "rust", extensions: &["rs"], comment: "//", programs: &[RUSTC], compiled: true, diagnostics: Some(diagnostics::rustc) },
+    Language { name: "c", extensions: &["c"], comment: "//", programs: &[GCC], compiled: true, diagnostics: Some(diagnostics::gcc) },
+    Language { name: "cpp", extensions: &["cpp", "cc", "cxx"], comment: "//", programs: &[GXX], compiled: true, diagnostics: Some(diagnostics::gcc) },
+    Language { name: "java", extensions: &["java"], comment: "//", programs: &[JAVAC, JAVA], compiled: true, diagnostics: Some(diagnostics::javac) },
+    Language { name: "php", extensions: &["php"], comment: "//", programs: &[PHP], compiled: false, diagnostics: None },
+    Language { name: "ruby", extensions: &["rb"], comment: "#", programs: &[RUBY], compiled: false, diagnostics: None },
+    Language { name: "shell", extensions: &["sh"], comment: "#", programs: &[BASH], compiled: false, diagnostics: None },
+    Language { name: "powershell", extensions: &["ps1"], comment: "#", programs: &[POWERSHELL], compiled: false, diagnostics: None },
+    Language { name: "lua", extensions: &["lua"], comment: "--", programs: &[LUA], compiled: false, diagnostics: None },
+    Language { name: "perl", extensions: &["pl"], comment: "#", programs: &[PERL], compiled: false, diagnostics: None },
+    Language { name: "r", extensions: &["r"], comment: "#", programs: &[RSCRIPT], compiled: false, diagnostics: None },
+    Language { name: "swift", extensions: &["swift"], comment: "//", programs: &[SWIFT], compiled: false, diagnostics: None },
+    Language { name: "dart", extensions: &["dart"], comment: "//", programs: &[DART], compiled: false, diagnostics: None },
+    Language { name: "zig", extensions: &["zig"], comment: "//", programs: &[ZIG], compiled: true, diagnostics: None },
+    Language { name: "haskell", extensions: &["hs"], comment: "--", programs: &[RUNHASKELL], compiled: false, diagnostics: None },
+    Language { name: "julia", extensions: &["jl"], comment: "#", programs: &[JULIA], compiled: false, diagnostics: None },
+    Language { name: "elixir", extensions: &["ex", "exs"], comment: "#", programs: &[ELIXIR], compiled: false, diagnostics: None },
+    Language { name: "crystal", extensions: &["cr"], comment: "#", programs: &[CRYSTAL], compiled: true, diagnostics: None },
+    Language { name: "scala", extensions: &["scala"], comment: "//", programs: &[SCALA, JAVA], compiled: false, diagnostics: None },
+    Language { name: "groovy", extensions: &["groovy"], comment: "//", programs: &[GROOVY, JAVA], compiled: false, diagnostics: None },
+    Language { name: "clojure", extensions: &["clj"], comment: ";", programs: &[CLOJURE, JAVA], compiled: false, diagnostics: None },
+    Language { name: "racket", extensions: &["rkt"], comment: ";", programs: &[RACKET], compiled: false, diagnostics: None },
+    Language { name: "ocaml", extensions: &["ml"], comment: "(*", programs: &[OCAML], compiled: false, diagnostics: None },
+    Language { name: "erlang", extensions: &["erl"], comment: "%", programs: &[ESCRIPT], compiled: false, diagnostics: None },
+    Language { name: "tcl", extensions: &["tcl"], comment: "#", programs: &[TCLSH], compiled: false, diagnostics: None },
+    Language { name: "kotlin", extensions: &["kt", "kts"], comment: "//", programs: &[KOTLINC, JAVA], compiled: true, diagnostics: None },
+    Language { name: "nim", extensions: &["nim"], comment: "#", programs: &[NIM], compiled: true, diagnostics: None },
+    Language { name: "d", extensions: &["d"], comment: "//", programs: &[DMD], compiled: true, diagnostics: None },
+    Language { name: "fsharp", extensions: &["fs", "fsx"], comment: "//", programs: &[DOTNET], compiled: false, diagnostics: None },
+    Language { name: "csharp", extensions: &["cs"], comment: "//", programs: &[DOTNET], compiled: false, diagnostics: None },
+];
+
+pub fn find_language(ext: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|lang| lang.extensions.contains(&ext))
+}
+
+pub fn language_named(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|lang| lang.name == name)
+}
+
 fn is_win() -> bool {
     cfg!(target_os = "windows")
 }
 
-fn bin(name: &str) -> String {
+/// File name of a compiled executable.
+fn bin_name(name: &str) -> String {
     if is_win() {
         format!("{}.exe", name)
     } else {
-        format!("./{}", name)
+        name.to_string()
     }
 }
 
-fn escape_for_shell(path: &str) -> String {
-    if is_win() {
-        if path.contains(' ') {
-            format!("\"{}\"", path)
-        } else {
-            path.to_string()
+/// Where commands for compiled languages put their artifacts.
+#[derive(Clone, Copy)]
+enum BuildDirs {
+    /// A fresh private directory for each command.
+    Create,
+    /// Only a name standing for one, for commands that are shown but not run.
+    Placeholder,
+}
+
+impl BuildDirs {
+    fn next(self) -> Result<Arc<BuildDirGuard>> {
+        match self {
+            BuildDirs::Create => Ok(BuildDirGuard::create()?),
+            BuildDirs::Placeholder => Ok(BuildDirGuard::placeholder()),
         }
-    } else {
-        format!("'{}'", path.replace('\'', "'\\''"))
     }
 }
 
-pub fn get_command(ext: &str, file_path: &Path) -> Option<CommandSpec> {
+/// Compiles `source` into an executable in a build directory and runs it.
+fn native(dirs: BuildDirs, compiler: &str, source: String) -> Result<CommandSpec> {
+    build_and_run(dirs, compiler, |exe, _| vec![source, "-o".to_string(), exe])
+}
+
+/// Like [`native`], for compilers whose arguments `args` makes from the
+/// executable's path and the build directory.
+fn build_and_run(dirs: BuildDirs, compiler: &str, args: impl FnOnce(String, String) -> Vec<String>) -> Result<CommandSpec> {
+    let build_dir = dirs.next()?;
+    let exe = build_dir.path().join(bin_name("main")).display().to_string();
+    let dir = build_dir.path().display().to_string();
+    
+    Ok(CommandSpec::compiled(
+        Stage::new(compiler.to_string(), args(exe.clone(), dir)),
+        exe,
+        vec![],
+    ).with_build_dir(build_dir))
+}
+
+/// The command that runs `file_path`, with the file's own `directives`, the
+/// selected profile and any `[languages.<name>]` overrides from `config`
+/// applied.
+pub fn get_command(ext: &str, file_path: &Path, config: &Config, directives: &Directives) -> Result<CommandSpec> {
+    resolve_command(ext, file_path, config, directives, BuildDirs::Create)
+}
+
+/// Like [`get_command`], for showing the command rather than running it:
+/// nothing is created on disk, and the build directory is only a placeholder
+/// name, as every run gets a fresh one.
+pub fn preview_command(ext: &str, file_path: &Path, config: &Config, directives: &Directives) -> Result<CommandSpec> {
+    resolve_command(ext, file_path, config, directives, BuildDirs::Placeholder)
+}
+
+fn resolve_command(
+    ext: &str,
+    file_path: &Path,
+    config: &Config,
+    directives: &Directives,
+    dirs: BuildDirs,
+) -> Result<CommandSpec> {
+    let language = find_language(ext).ok_or_else(|| CodeRunnerError::UnsupportedFileType(ext.to_string()))?;
+    let mut spec = build_command(ext, file_path, dirs)?.with_language(language.name);
+    let overrides = config.language(language.name);
+    
+    if let Some(runner) = overrides.and_then(|overrides| overrides.runner.as_deref()) {
+        spec = apply_runner(spec, runner, file_path);
+    }
+    add_build_flags(&mut spec, config.profile_flags(language.name), file_path);
+    add_build_flags(&mut spec, &directives.flags, file_path);
+    spec.args.extend(directives.args.iter().cloned());
+    spec.stdin = directives.stdin.clone();
+    spec.timeout = directives.timeout;
+    if let Some(overrides) = overrides {
+        spec = apply_compiler(spec, overrides, file_path);
+    }
+    Ok(spec)
+}
+
+/// The programs running a `language` file needs under `config`, in the order
+/// they are used: those the language declares, with the compiler and runner
+/// overrides applied the way [`get_command`] applies them.
+pub fn required_programs(language: &Language, config: &Config) -> Result<Vec<String>> {
+    let mut programs: Vec<String> = language.programs.iter().map(|program| program.name.to_string()).collect();
+    let overrides = config.language(language.name);
+    
+    if let Some(compiler) = overrides.and_then(|overrides| overrides.compiler.as_deref()).filter(|_| language.compiled) {
+        if compiler.trim().is_empty() {
+            return Err(CodeRunnerError::ConfigError(format!("languages.{}.compiler is empty", language.name)));
+        }
+        programs[0] = compiler.to_string();
+    }
+    
+    // A built executable is wrapped by the runner; any other program is replaced.
+    if let Some(runner) = overrides.and_then(|overrides| overrides.runner.as_deref()).and_then(|runner| runner.split_whitespace().next()) {
+        match (language.compiled, programs.len()) {
+            (true, 1) => programs.push(runner.to_string()),
+            (true, _) => programs[1] = runner.to_string(),
+            (false, _) => programs[0] = runner.to_string(),
+        }
+    }
+    
+    let mut unique = Vec::new();
+    for program in programs {
+        if !unique.contains(&program) {
+            unique.push(program);
+        }
+    }
+    Ok(unique)
+}
+
+/// Adds profile flags to the compile stage, or for languages that build and
+/// run in one step (`runhaskell`), just before the source file.
+fn add_build_flags(spec: &mut CommandSpec, flags: &[String], file_path: &Path) {
+    if flags.is_empty() {
+        return;
+    }
+    
+    match spec.compile.as_mut() {
+        Some(compile) => add_compiler_flags(&mut compile.args, flags, file_path),
+        None => {
+            let source = file_path.display().to_string();
+            let position = spec.args.iter().rposition(|arg| *arg == source).unwrap_or(spec.args.len());
+            spec.args.splice(position..position, flags.iter().cloned());
+        }
+    }
+}
+
+/// Appends `flags` to a compile stage's `args`, or inserts them before the
+/// source file for compilers that take it last (`go build`, `nim c`), as
+/// those read anything after it as more files.
+fn add_compiler_flags(args: &mut Vec<String>, flags: &[String], file_path: &Path) {
+    let source = file_path.display().to_string();
+    let position = match args.last() {
+        Some(last) if *last == source => args.len() - 1,
+        _ => args.len(),
+    };
+    args.splice(position..position, flags.iter().cloned());
+}
+
+fn apply_compiler(mut spec: CommandSpec, overrides: &LanguageConfig, file_path: &Path) -> CommandSpec {
+    if let Some(compile) = spec.compile.as_mut() {
+        if let Some(compiler) = &overrides.compiler {
+            compile.program = compiler.clone();
+        }
+        add_compiler_flags(&mut compile.args, &overrides.compiler_flags, file_path);
+    }
+    spec
+}
+
+/// Runs the file with `runner`. An interpreter is replaced together with its
+/// own arguments, so `npx tsx main.ts` becomes `deno run main.ts` with
+/// `runner = "deno run"`; only the source file and what follows it are kept.
+/// A built executable is wrapped instead, and the program that runs other
+/// build outputs, like `java -cp <dir> Main`, is replaced keeping its
+/// arguments.
+fn apply_runner(mut spec: CommandSpec, runner: &str, file_path: &Path) -> CommandSpec {
+    let mut words = runner.split_whitespace().map(str::to_string);
+    let Some(program) = words.next() else {
+        return spec;
+    };
+    
+    let builds_executable = spec.build_dir.as_ref()
+        .is_some_and(|dir| Path::new(&spec.program).starts_with(dir));
+    let mut args: Vec<String> = words.collect();
+    if builds_executable {
+        args.push(spec.program.clone());
+    } else if spec.compile.is_none() {
+        let source = file_path.display().to_string();
+        let start = spec.args.iter().position(|arg| *arg == source).unwrap_or(0);
+        spec.args.drain(..start);
+    }
+    args.append(&mut spec.args);
+    spec.program = program;
+    spec.args = args;
+    spec
+}
+
+fn build_command(ext: &str, file_path: &Path, dirs: BuildDirs) -> Result<CommandSpec> {
     let path_str = file_path.display().to_string();
     
     match ext {
-        "js" => Some(CommandSpec::new("node".to_string(), vec![path_str])),
-        "ts" => Some(CommandSpec::new("npx".to_string(), vec!["tsx".to_string(), path_str])),
-        "py" => Some(CommandSpec::new("python".to_string(), vec![path_str])),
-        "go" => Some(CommandSpec::new("go".to_string(), vec!["run".to_string(), path_str])),
+        "js" => Ok(CommandSpec::new("node".to_string(), vec![path_str])),
+        "ts" => Ok(CommandSpec::new("npx".to_string(), vec!["tsx".to_string(), path_str])),
+        "py" => Ok(CommandSpec::new("python".to_string(), vec![path_str])),
+        "go" => build_and_run(dirs, "go", |exe, _| vec!["build".to_string(), "-o".to_string(), exe, path_str]),
         
-        "rs" => Some(CommandSpec::new(
-            if is_win() { "cmd".to_string() } else { "sh".to_string() },
-            if is_win() {
-                vec!["/C".to_string(), format!("rustc {} -o main && {}", escape_for_shell(&path_str), bin("main"))]
-            } else {
-                vec!["-c".to_string(), format!("rustc {} -o main && {}", escape_for_shell(&path_str), bin("main"))]
-            }
-        )),
+        "rs" => native(dirs, "rustc", path_str),
         
-        "c" => Some(CommandSpec::new(
-            if is_win() { "cmd".to_string() } else { "sh".to_string() },
-            if is_win() {
-                vec!["/C".to_string(), format!("gcc {} -o main && {}", escape_for_shell(&path_str), bin("main"))]
-            } else {
-                vec!["-c".to_string(), format!("gcc {} -o main && {}", escape_for_shell(&path_str), bin("main"))]
-            }
-        )),
+        "c" => native(dirs, "gcc", path_str),
         
-        "cpp" | "cc" | "cxx" => Some(CommandSpec::new(
-            if is_win() { "cmd".to_string() } else { "sh".to_string() },
-            if is_win() {
-                vec!["/C".to_string(), format!("g++ {} -o main && {}", escape_for_shell(&path_str), bin("main"))]
-            } else {
-                vec!["-c".to_string(), format!("g++ {} -o main && {}", escape_for_shell(&path_str), bin("main"))]
-            }
-        )),
+        "cpp" | "cc" | "cxx" => native(dirs, "g++", path_str),
         
         "java" => {
             let cls = file_path.file_stem()
                 .and_then(|s| s.to_str())
                 .unwrap_or("Main")
                 .to_string();
+            let build_dir = dirs.next()?;
+            let class_dir = build_dir.path().display().to_string();
             
-            Some(CommandSpec::new(
-                if is_win() { "cmd".to_string() } else { "sh".to_string() },
-                if is_win() {
-                    vec!["/C".to_string(), format!("javac {} && java {}", escape_for_shell(&path_str), cls)]
-                } else {
-                    vec!["-c".to_string(), format!("javac {} && java {}", escape_for_shell(&path_str), cls)]
-                }
-            ))
+            Ok(CommandSpec::compiled(
+                Stage::new("javac".to_string(), vec!["-d".to_string(), class_dir.clone(), path_str]),
+                "java".to_string(),
+                vec!["-cp".to_string(), class_dir, cls],
+            ).with_build_dir(build_dir))
         },
         
-        "php" => Some(CommandSpec::new("php".to_string(), vec![path_str])),
-        "rb" => Some(CommandSpec::new("ruby".to_string(), vec![path_str])),
-        "sh" => Some(CommandSpec::new("bash".to_string(), vec![path_str])),
-        "ps1" => Some(CommandSpec::new(
+        "php" => Ok(CommandSpec::new("php".to_string(), vec![path_str])),
+        "rb" => Ok(CommandSpec::new("ruby".to_string(), vec![path_str])),
+        "sh" => Ok(CommandSpec::new("bash".to_string(), vec![path_str])),
+        "ps1" => Ok(CommandSpec::new(
             "powershell".to_string(),
             vec!["-ExecutionPolicy".to_string(), "Bypass".to_string(), "-File".to_string(), path_str]
         )),
-        "lua" => Some(CommandSpec::new("lua".to_string(), vec![path_str])),
-        "pl" => Some(CommandSpec::new("perl".to_string(), vec![path_str])),
-        "r" => Some(CommandSpec::new("Rscript".to_string(), vec![path_str])),
-        "swift" => Some(CommandSpec::new("swift".to_string(), vec![path_str])),
-        "dart" => Some(CommandSpec::new("dart".to_string(), vec!["run".to_string(), path_str])),
-        "zig" => Some(CommandSpec::new("zig".to_string(), vec!["run".to_string(), path_str])),
-        "hs" => Some(CommandSpec::new("runhaskell".to_string(), vec![path_str])),
-        "jl" => Some(CommandSpec::new("julia".to_string(), vec![path_str])),
-        "ex" | "exs" => Some(CommandSpec::new("elixir".to_string(), vec![path_str])),
-        "cr" => Some(CommandSpec::new("crystal".to_string(), vec!["run".to_string(), path_str])),
-        "scala" => Some(CommandSpec::new("scala".to_string(), vec![path_str])),
-        "groovy" => Some(CommandSpec::new("groovy".to_string(), vec![path_str])),
-        "clj" => Some(CommandSpec::new("clojure".to_string(), vec![path_str])),
-        "rkt" => Some(CommandSpec::new("racket".to_string(), vec![path_str])),
-        "ml" => Some(CommandSpec::new("ocaml".to_string(), vec![path_str])),
-        "erl" => Some(CommandSpec::new("escript".to_string(), vec![path_str])),
-        "tcl" => Some(CommandSpec::new("tclsh".to_string(), vec![path_str])),
+        "lua" => Ok(CommandSpec::new("lua".to_string(), vec![path_str])),
+        "pl" => Ok(CommandSpec::new("perl".to_string(), vec![path_str])),
+        "r" => Ok(CommandSpec::new("Rscript".to_string(), vec![path_str])),
+        "swift" => Ok(CommandSpec::new("swift".to_string(), vec![path_str])),
+        "dart" => Ok(CommandSpec::new("dart".to_string(), vec!["run".to_string(), path_str])),
+        "zig" => build_and_run(dirs, "zig", |exe, _| vec!["build-exe".to_string(), format!("-femit-bin={}", exe), path_str]),
+        "hs" => Ok(CommandSpec::new("runhaskell".to_string(), vec![path_str])),
+        "jl" => Ok(CommandSpec::new("julia".to_string(), vec![path_str])),
+        "ex" | "exs" => Ok(CommandSpec::new("elixir".to_string(), vec![path_str])),
+        "cr" => build_and_run(dirs, "crystal", |exe, _| vec!["build".to_string(), "-o".to_string(), exe, path_str]),
+        "scala" => Ok(CommandSpec::new("scala".to_string(), vec![path_str])),
+        "groovy" => Ok(CommandSpec::new("groovy".to_string(), vec![path_str])),
+        "clj" => Ok(CommandSpec::new("clojure".to_string(), vec![path_str])),
+        "rkt" => Ok(CommandSpec::new("racket".to_string(), vec![path_str])),
+        "ml" => Ok(CommandSpec::new("ocaml".to_string(), vec![path_str])),
+        "erl" => Ok(CommandSpec::new("escript".to_string(), vec![path_str])),
+        "tcl" => Ok(CommandSpec::new("tclsh".to_string(), vec![path_str])),
         
-        "kt" | "kts" => Some(CommandSpec::new(
-            if is_win() { "cmd".to_string() } else { "sh".to_string() },
-            if is_win() {
-                vec!["/C".to_string(), format!("kotlinc {} -include-runtime -d main.jar && java -jar main.jar", escape_for_shell(&path_str))]
-            } else {
-                vec!["-c".to_string(), format!("kotlinc {} -include-runtime -d main.jar && java -jar main.jar", escape_for_shell(&path_str))]
-            }
-        )),
+        "kt" | "kts" => {
+            let build_dir = dirs.next()?;
+            let jar = build_dir.path().join("main.jar").display().to_string();
+            
+            Ok(CommandSpec::compiled(
+                Stage::new(
+                    "kotlinc".to_string(),
+                    vec![path_str, "-include-runtime".to_string(), "-d".to_string(), jar.clone()],
+                ),
+                "java".to_string(),
+                vec!["-jar".to_string(), jar],
+            ).with_build_dir(build_dir))
+        },
         
-        "nim" => Some(CommandSpec::new(
-            if is_win() { "cmd".to_string() } else { "sh".to_string() },
-            if is_win() {
-                vec!["/C".to_string(), format!("nim c -r {}", escape_for_shell(&path_str))]
-            } else {
-                vec!["-c".to_string(), format!("nim c -r {}", escape_for_shell(&path_str))]
-            }
-        )),
+        "nim" => build_and_run(dirs, "nim", |exe, _| vec!["c".to_string(), format!("-o:{}", exe), path_str]),
         
-        "d" => Some(CommandSpec::new(
-            if is_win() { "cmd".to_string() } else { "sh".to_string() },
-            if is_win() {
-                vec!["/C".to_string(), format!("dmd -run {}", escape_for_shell(&path_str))]
-            } else {
-                vec!["-c".to_string(), format!("dmd -run {}", escape_for_shell(&path_str))]
-            }
-        )),
+        "d" => build_and_run(dirs, "dmd", |exe, dir| vec![path_str, format!("-of={}", exe), format!("-od={}", dir)]),
         
-        "fs" | "fsx" => Some(CommandSpec::new(
+        "fs" | "fsx" => Ok(CommandSpec::new(
             "dotnet".to_string(),
             vec!["fsi".to_string(), path_str]
         )),
         
-        "cs" => Some(CommandSpec::new(
+        "cs" => Ok(CommandSpec::new(
             "dotnet".to_string(),
             vec!["script".to_string(), path_str]
         )),
         
-        _ => None,
+        _ => Err(CodeRunnerError::UnsupportedFileType(ext.to_string())),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_language(name: &str, overrides: LanguageConfig) -> Config {
+        let mut config = Config::default();
+        config.languages.insert(name.to_string(), overrides);
+        config
+    }
+
+    #[test]
+    fn test_runner_replaces_interpreter() {
+        let config = with_language("python", LanguageConfig {
+            runner: Some("python3 -X dev".to_string()),
+            ..LanguageConfig::default()
+        });
+        let spec = get_command("py", Path::new("/src/app.py"), &config, &Directives::default()).unwrap();
+        assert_eq!(spec.display_string(), "python3 -X dev /src/app.py");
+    }
+
+    #[test]
+    fn test_runner_replaces_interpreter_args() {
+        let config = with_language("typescript", LanguageConfig {
+            runner: Some("deno run".to_string()),
+            ..LanguageConfig::default()
+        });
+        let directives = Directives { args: vec!["--fast".to_string()], ..Directives::default() };
+        let spec = get_command("ts", Path::new("/src/main.ts"), &config, &directives).unwrap();
+        assert_eq!(spec.display_string(), "deno run /src/main.ts --fast");
+
+        let config = with_language("haskell", LanguageConfig {
+            runner: Some("stack runghc".to_string()),
+            ..LanguageConfig::default()
+        });
+        let directives = Directives { flags: vec!["-Wall".to_string()], ..Directives::default() };
+        let spec = get_command("hs", Path::new("/src/main.hs"), &config, &directives).unwrap();
+        assert_eq!(spec.display_string(), "stack runghc -Wall /src/main.hs");
+
+        let config = with_language("java", LanguageConfig {
+            runner: Some("java -Xmx1g".to_string()),
+            ..LanguageConfig::default()
+        });
+        let spec = get_command("java", Path::new("/src/Main.java"), &config, &Directives::default()).unwrap();
+        assert_eq!(spec.args[..2], ["-Xmx1g", "-cp"]);
+        assert_eq!(spec.args.last().unwrap(), "Main");
+    }
+
+    #[test]
+    fn test_compiler_overrides_and_wrapping_runner() {
+        let config = with_language("c", LanguageConfig {
+            compiler: Some("clang".to_string()),
+            compiler_flags: vec!["-O2".to_string(), "-lm".to_string()],
+            runner: Some("valgrind -q".to_string()),
+            ..LanguageConfig::default()
+        });
+        let spec = get_command("c", Path::new("/src/main.c"), &config, &Directives::default()).unwrap();
+        let compile = spec.compile.as_ref().unwrap();
+        assert_eq!(compile.program, "clang");
+        assert_eq!(compile.args[compile.args.len() - 2..], ["-O2", "-lm"]);
+        assert_eq!(spec.program, "valgrind");
+        assert_eq!(spec.args[0], "-q");
+        assert!(spec.args[1].ends_with(&bin_name("main")));
+    }
+
+    #[test]
+    fn test_profile_flags() {
+        let config = Config { profile: Some("release".to_string()), ..Config::default() };
+
+        let spec = get_command("rs", Path::new("/src/main.rs"), &config, &Directives::default()).unwrap();
+        assert!(spec.compile.unwrap().args.ends_with(&["-C".to_string(), "opt-level=3".to_string()]));
+
+        let spec = get_command("go", Path::new("/src/main.go"), &config, &Directives::default()).unwrap();
+        let compile = spec.compile.unwrap();
+        assert_eq!(compile.args[0], "build");
+        assert_eq!(compile.args[3..], ["-trimpath", "/src/main.go"]);
+
+        let spec = get_command("hs", Path::new("/src/main.hs"), &config, &Directives::default()).unwrap();
+        assert_eq!(spec.display_string(), "runhaskell --ghc-arg=-O2 /src/main.hs");
+    }
+
+    #[test]
+    fn test_one_step_builds_are_split() {
+        for ext in ["go", "nim", "d", "cr", "zig"] {
+            let spec = get_command(ext, Path::new("/src/main"), &Config::default(), &Directives::default()).unwrap();
+            assert!(spec.compile.is_some(), "{}", ext);
+            assert!(spec.program.ends_with(&bin_name("main")), "{}", ext);
+            assert!(spec.args.is_empty(), "{}", ext);
+        }
+    }
+
+    #[test]
+    fn test_preview_creates_no_build_dir() {
+        for ext in ["c", "java", "kt", "go"] {
+            let spec = preview_command(ext, Path::new("/src/main"), &Config::default(), &Directives::default()).unwrap();
+            let build_dir = spec.build_dir.unwrap();
+            assert!(build_dir.ends_with("code-runner-XXXXXX"), "{}", ext);
+            assert!(!build_dir.exists(), "{}", ext);
+        }
+    }
+
+    #[test]
+    fn test_required_programs() {
+        let required = |ext: &str, config: &Config| required_programs(find_language(ext).unwrap(), config).unwrap();
+        let config = Config::default();
+        assert_eq!(required("java", &config), ["javac", "java"]);
+        assert_eq!(required("ts", &config), ["npx", "node"]);
+        assert_eq!(required("nim", &config), ["nim"]);
+
+        let config = with_language("c", LanguageConfig {
+            compiler: Some("clang".to_string()),
+            runner: Some("valgrind -q".to_string()),
+            ..LanguageConfig::default()
+        });
+        assert_eq!(required("c", &config), ["clang", "valgrind"]);
+
+        let config = with_language("kotlin", LanguageConfig {
+            runner: Some("java -Xmx1g".to_string()),
+            ..LanguageConfig::default()
+        });
+        assert_eq!(required("kt", &config), ["kotlinc", "java"]);
+        let config = with_language("typescript", LanguageConfig {
+            runner: Some("deno run".to_string()),
+            ..LanguageConfig::default()
+        });
+        assert_eq!(required("ts", &config), ["deno", "node"]);
+
+        let config = with_language("c", LanguageConfig { compiler: Some(" ".to_string()), ..LanguageConfig::default() });
+        assert!(required_programs(find_language("c").unwrap(), &config).is_err());
+    }
+
+    #[test]
+    fn test_required_programs_match_the_commands() {
+        for language in LANGUAGES {
+            let ext = language.extensions[0];
+            let spec = preview_command(ext, Path::new("main"), &Config::default(), &Directives::default()).unwrap();
+            assert_eq!(language.compiled, spec.compile.is_some(), "{}", language.name);
+            
+            let required = required_programs(language, &Config::default()).unwrap();
+            let build_dir = spec.build_dir.clone().unwrap_or_default();
+            for stage in spec.stages().into_iter().filter(|stage| !Path::new(&stage.program).starts_with(&build_dir)) {
+                assert!(required.contains(&stage.program), "{}: {}", language.name, stage.program);
+            }
+        }
+    }
+
+    #[test]
+    fn test_other_languages_are_untouched() {
+        let config = with_language("python", LanguageConfig {
+            runner: Some("pypy3".to_string()),
+            ..LanguageConfig::default()
+        });
+        let spec = get_command("rb", Path::new("/src/app.rb"), &config, &Directives::default()).unwrap();
+        assert_eq!(spec.display_string(), "ruby /src/app.rb");
+    }
+}
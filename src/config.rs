@@ -1,41 +1,245 @@
+use crate::env::Environment;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use dirs;
 
+/// Settings missing from a config file keep their default values.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub timeout: u64,
+    /// Timeout given on the command line, which also wins over the file's
+    /// own `timeout=` directive. Never read from or written to a file.
+    #[serde(skip)]
+    pub timeout_override: Option<u64>,
     pub max_file_size_mb: u64,
     pub cleanup_artifacts: bool,
     pub silent_mode: bool,
     pub check_installed: bool,
+    pub max_output_bytes: u64,
+    pub kill_on_output_limit: bool,
+    pub env: BTreeMap<String, String>,
+    pub env_file: Option<PathBuf>,
+    pub clear_env: bool,
+    pub cwd: CwdPolicy,
+    /// Run every file as this language, whatever its extension. Only set by
+    /// `--lang`, never read from or written to a file.
+    #[serde(skip)]
+    pub lang: Option<String>,
+    /// Record runs for `code-runner history`.
+    pub history: bool,
+    /// Only run files approved with `code-runner allow`; see [`crate::trust`].
+    pub require_trust: bool,
+    /// Directories whose files run without approval.
+    pub trusted_roots: Vec<PathBuf>,
+    /// Run files given as a symlink, rather than refusing them.
+    pub follow_symlinks: bool,
+    /// Directories files must be in to run; empty allows any.
+    pub allowed_roots: Vec<PathBuf>,
+    pub languages: BTreeMap<String, LanguageConfig>,
+    pub problem_matchers: Vec<ProblemMatcher>,
+    /// Build profile to use, one of the keys of `profiles`.
+    pub profile: Option<String>,
+    /// Compiler flags per profile, then per language.
+    pub profiles: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+}
+
+/// A `[[problem_matchers]]` entry: regexes whose capture groups locate
+/// problems in a program's output, in the spirit of VS Code task matchers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProblemMatcher {
+    /// Languages the matcher applies to; empty means every language.
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Severity used when no group captures one.
+    #[serde(default)]
+    pub severity: Option<String>,
+    /// Patterns that must match consecutive lines, in order.
+    pub pattern: Vec<MatcherPattern>,
+}
+
+impl ProblemMatcher {
+    pub fn applies_to(&self, language: &str) -> bool {
+        self.languages.is_empty() || self.languages.iter().any(|name| name == language)
+    }
+}
+
+/// One line of a problem matcher, with the capture group index of each field
+/// it provides.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatcherPattern {
+    pub regexp: String,
+    #[serde(default)]
+    pub file: Option<usize>,
+    #[serde(default)]
+    pub line: Option<usize>,
+    #[serde(default)]
+    pub column: Option<usize>,
+    #[serde(default)]
+    pub severity: Option<usize>,
+    #[serde(default)]
+    pub message: Option<usize>,
+    /// On the last pattern: keep matching following lines, reporting one
+    /// problem per line.
+    #[serde(default, rename = "loop")]
+    pub repeat: bool,
+}
+
+/// Where programs are started from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum CwdPolicy {
+    /// The directory containing the source file.
+    FileDir,
+    /// The nearest ancestor with a VCS marker, falling back to the file's directory.
+    WorkspaceRoot,
+    /// Whatever directory code-runner itself was started in.
+    #[default]
+    Inherit,
+    Path(PathBuf),
+}
+
+impl CwdPolicy {
+    /// Returns the directory to run in for `file`, or `None` to inherit ours.
+    pub fn resolve(&self, file: &Path) -> crate::Result<Option<PathBuf>> {
+        let file_dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        
+        let dir = match self {
+            CwdPolicy::Inherit => return Ok(None),
+            CwdPolicy::FileDir => file_dir,
+            CwdPolicy::WorkspaceRoot => crate::utils::find_workspace_root(&file_dir).unwrap_or(file_dir),
+            CwdPolicy::Path(path) => path.clone(),
+        };
+        
+        if !dir.is_dir() {
+            return Err(crate::CodeRunnerError::InvalidPath(
+                format!("Working directory does not exist: {}", dir.display())
+            ));
+        }
+        
+        Ok(Some(dir))
+    }
+}
+
+impl From<String> for CwdPolicy {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "file_dir" => CwdPolicy::FileDir,
+            "workspace_root" => CwdPolicy::WorkspaceRoot,
+            "inherit" => CwdPolicy::Inherit,
+            _ => CwdPolicy::Path(PathBuf::from(value)),
+        }
+    }
+}
+
+impl From<CwdPolicy> for String {
+    fn from(policy: CwdPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
+impl fmt::Display for CwdPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CwdPolicy::FileDir => write!(f, "file_dir"),
+            CwdPolicy::WorkspaceRoot => write!(f, "workspace_root"),
+            CwdPolicy::Inherit => write!(f, "inherit"),
+            CwdPolicy::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Settings from a `[languages.<name>]` table that apply to one language only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    pub timeout: Option<u64>,
+    pub check_installed: Option<bool>,
+    /// Command that runs the file in place of the default interpreter and its
+    /// arguments, e.g. `"pypy3"` or `"deno run"` (making `npx tsx main.ts`
+    /// `deno run main.ts`). For natively compiled languages it wraps the
+    /// built executable instead, e.g. `"valgrind -q"`, and for JVM languages
+    /// it replaces `java`, keeping the class path arguments.
+    pub runner: Option<String>,
+    /// Replaces the compiler of languages with a compile stage.
+    pub compiler: Option<String>,
+    /// Extra arguments appended to the compile stage.
+    pub compiler_flags: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub env_file: Option<PathBuf>,
+    pub clear_env: Option<bool>,
+    pub cwd: Option<CwdPolicy>,
+}
+
+fn default_profiles() -> BTreeMap<String, BTreeMap<String, Vec<String>>> {
+    let profile = |flags: &[(&str, &[&str])]| {
+        flags
+            .iter()
+            .map(|(language, flags)| {
+                (language.to_string(), flags.iter().map(|flag| flag.to_string()).collect())
+            })
+            .collect()
+    };
+    let sanitize: &[&str] = &["-fsanitize=address,undefined", "-fno-omit-frame-pointer", "-g"];
+
+    BTreeMap::from([
+        ("release".to_string(), profile(&[
+            ("c", &["-O2"]),
+            ("cpp", &["-O2"]),
+            ("rust", &["-C", "opt-level=3"]),
+            ("haskell", &["--ghc-arg=-O2"]),
+            ("go", &["-trimpath"]),
+        ])),
+        ("debug".to_string(), profile(&[
+            ("c", &["-g", "-O0"]),
+            ("cpp", &["-g", "-O0"]),
+            ("rust", &["-g", "-C", "opt-level=0"]),
+            ("go", &["-gcflags=all=-N -l"]),
+        ])),
+        ("asan".to_string(), profile(&[
+            ("c", sanitize),
+            ("cpp", sanitize),
+        ])),
+    ])
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             timeout: 30,
+            timeout_override: None,
             max_file_size_mb: 100,
             cleanup_artifacts: true,
             silent_mode: false,
             check_installed: true,
+            max_output_bytes: 10 * 1024 * 1024,
+            kill_on_output_limit: false,
+            env: BTreeMap::new(),
+            env_file: None,
+            clear_env: false,
+            cwd: CwdPolicy::default(),
+            lang: None,
+            history: true,
+            require_trust: false,
+            trusted_roots: Vec::new(),
+            follow_symlinks: true,
+            allowed_roots: Vec::new(),
+            languages: BTreeMap::new(),
+            problem_matchers: Vec::new(),
+            profile: None,
+            profiles: default_profiles(),
         }
     }
 }
 
 impl Config {
-    pub fn load() -> Self {
-        if let Some(config_path) = Self::config_path() {
-            if config_path.exists() {
-                if let Ok(content) = fs::read_to_string(&config_path) {
-                    if let Ok(config) = toml::from_str(&content) {
-                        return config;
-                    }
-                }
-            }
-        }
-        Self::default()
+    /// The layered configuration for the current directory, without CLI
+    /// overrides. See [`crate::layers::load`].
+    pub fn load() -> crate::Result<Self> {
+        Ok(crate::layers::load(None, &[])?.config)
     }
     
     pub fn save(&self) -> crate::Result<()> {
@@ -50,11 +254,172 @@ impl Config {
         Ok(())
     }
     
-    fn config_path() -> Option<PathBuf> {
+    pub fn language(&self, language: &str) -> Option<&LanguageConfig> {
+        self.languages.get(language)
+    }
+    
+    pub fn timeout_for(&self, language: &str) -> u64 {
+        self.language(language).and_then(|lang| lang.timeout).unwrap_or(self.timeout)
+    }
+
+    /// The timeout for a `language` file whose directive asks for `directive`:
+    /// a command line timeout, then the directive, then the config.
+    pub fn stage_timeout(&self, language: &str, directive: Option<u64>) -> u64 {
+        self.timeout_override
+            .or(directive)
+            .unwrap_or_else(|| self.timeout_for(language))
+    }
+    
+    pub fn check_installed_for(&self, language: &str) -> bool {
+        self.language(language).and_then(|lang| lang.check_installed).unwrap_or(self.check_installed)
+    }
+    
+    /// Checks that the selected profile, if any, is defined.
+    pub fn check_profile(&self) -> crate::Result<()> {
+        match &self.profile {
+            Some(name) if !self.profiles.contains_key(name) => Err(crate::CodeRunnerError::ConfigError(format!(
+                "Unknown profile '{}', expected one of: {}",
+                name,
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Flags the selected profile adds when building `language`.
+    pub fn profile_flags(&self, language: &str) -> &[String] {
+        self.profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+            .and_then(|profile| profile.get(language))
+            .map_or(&[], Vec::as_slice)
+    }
+    
+    pub fn cwd_for(&self, language: &str) -> &CwdPolicy {
+        self.language(language).and_then(|lang| lang.cwd.as_ref()).unwrap_or(&self.cwd)
+    }
+    
+    /// Resolves the environment for a run. Language settings win over global
+    /// ones, and explicit `env` entries win over `env_file` contents.
+    pub fn environment(&self, language: &str) -> crate::Result<Environment> {
+        let mut environment = Environment {
+            clear: self.clear_env,
+            ..Environment::default()
+        };
+        
+        if let Some(env_file) = &self.env_file {
+            environment.merge_file(env_file)?;
+        }
+        environment.vars.extend(self.env.clone());
+        
+        if let Some(lang) = self.language(language) {
+            if let Some(env_file) = &lang.env_file {
+                environment.merge_file(env_file)?;
+            }
+            environment.vars.extend(lang.env.clone());
+            if let Some(clear) = lang.clear_env {
+                environment.clear = clear;
+            }
+        }
+        
+        Ok(environment)
+    }
+    
+    /// The user config file, `~/.config/code-runner/config.toml` on Linux.
+    pub fn config_path() -> Option<PathBuf> {
         dirs::config_dir().map(|mut path| {
             path.push("code-runner");
             path.push("config.toml");
             path
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problem_matchers_parse() {
+        let config: Config = toml::from_str(r#"
+            timeout = 30
+            max_file_size_mb = 100
+            cleanup_artifacts = true
+            silent_mode = false
+            check_installed = true
+
+            [[problem_matchers]]
+            languages = ["lua"]
+
+            [[problem_matchers.pattern]]
+            regexp = '^In (\S+):$'
+            file = 1
+
+            [[problem_matchers.pattern]]
+            regexp = '^  (\d+): (.*)$'
+            line = 1
+            message = 2
+            loop = true
+        "#).unwrap();
+
+        let matcher = &config.problem_matchers[0];
+        assert!(matcher.applies_to("lua") && !matcher.applies_to("python"));
+        assert_eq!(matcher.pattern.len(), 2);
+        assert!(matcher.pattern[1].repeat);
+    }
+
+    #[test]
+    fn test_language_overrides() {
+        let config: Config = toml::from_str(
+            "timeout = 30\n[languages.kotlin]\ntimeout = 120\ncwd = \"file_dir\"\n[languages.python]\ncheck_installed = false"
+        ).unwrap();
+        assert_eq!(config.timeout_for("kotlin"), 120);
+        assert_eq!(config.timeout_for("go"), 30);
+        assert_eq!(config.cwd_for("kotlin"), &CwdPolicy::FileDir);
+        assert_eq!(config.cwd_for("python"), &CwdPolicy::Inherit);
+        assert!(!config.check_installed_for("python"));
+        assert!(config.check_installed_for("kotlin"));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let mut config = Config::default();
+        assert!(config.profile_flags("c").is_empty());
+
+        config.profile = Some("asan".to_string());
+        assert!(config.profile_flags("cpp").contains(&"-fsanitize=address,undefined".to_string()));
+        assert!(config.profile_flags("python").is_empty());
+
+        config.profile = Some("fast".to_string());
+        assert!(config.check_profile().unwrap_err().to_string().contains("asan, debug, release"));
+    }
+
+    #[test]
+    fn test_partial_config_keeps_defaults() {
+        let config: Config = toml::from_str("timeout = 5\n[languages.python]\nclear_env = true").unwrap();
+        assert_eq!(config.timeout, 5);
+        assert_eq!(config.max_output_bytes, Config::default().max_output_bytes);
+        assert!(config.check_installed);
+        assert_eq!(config.languages["python"].clear_env, Some(true));
+    }
+
+    #[test]
+    fn test_cwd_policy_round_trip() {
+        for value in ["file_dir", "workspace_root", "inherit", "/srv/app"] {
+            assert_eq!(String::from(CwdPolicy::from(value.to_string())), value);
+        }
+    }
+
+    #[test]
+    fn test_workspace_root_resolution() {
+        let root = tempfile::TempDir::new().unwrap();
+        let nested = root.path().join("src").join("bin");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(root.path().join(".git")).unwrap();
+
+        let file = nested.join("main.py");
+        let resolved = CwdPolicy::WorkspaceRoot.resolve(&file).unwrap();
+        assert_eq!(resolved.as_deref(), Some(root.path()));
+        assert_eq!(CwdPolicy::FileDir.resolve(&file).unwrap(), Some(nested));
+        assert_eq!(CwdPolicy::Inherit.resolve(&file).unwrap(), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Edits a config file in place for `code-runner config`, keeping its
+//! comments and layout.
+
+use crate::layers;
+use crate::{CodeRunnerError, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+/// The commented file written by `config init`.
+pub const TEMPLATE: &str = include_str!("../config/code-runner.toml");
+
+pub struct ConfigFile {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl ConfigFile {
+    /// Opens `path`, or starts an empty document if it doesn't exist yet.
+    pub fn open(path: &Path) -> Result<Self> {
+        let content = if path.exists() { fs::read_to_string(path)? } else { String::new() };
+        let doc = content.parse::<DocumentMut>().map_err(|e| {
+            CodeRunnerError::ConfigError(format!("{}: {}", path.display(), e.message()))
+        })?;
+        Ok(Self { path: path.to_path_buf(), doc })
+    }
+
+    /// Writes the commented template to `path`, refusing to replace an
+    /// existing file unless `force` is set.
+    pub fn init(path: &Path, force: bool) -> Result<()> {
+        if path.exists() && !force {
+            return Err(CodeRunnerError::ConfigError(format!(
+                "{} already exists (use --force to overwrite it)",
+                path.display()
+            )));
+        }
+        write(path, TEMPLATE)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sets `key` to `raw`, read as a TOML value (`5`, `true`, `["-O2"]`) or,
+    /// when that isn't valid for the setting, as a plain string.
+    pub fn set(&mut self, key: &str, raw: &str) -> Result<()> {
+        layers::check_key(key)?;
+
+        let parsed = raw.parse::<Value>().ok();
+        let mut attempt = self.with_value(key, parsed.unwrap_or_else(|| Value::from(raw)))?;
+        if attempt.validate().is_err() && !raw.trim_start().starts_with(['"', '\'']) {
+            attempt = self.with_value(key, Value::from(raw))?;
+        }
+        attempt.validate()?;
+
+        *self = attempt;
+        Ok(())
+    }
+
+    /// Removes `key`, returning whether it was present.
+    pub fn unset(&mut self, key: &str) -> Result<bool> {
+        let parts: Vec<&str> = key.split('.').collect();
+        let Some((last, parents)) = parts.split_last() else {
+            return Ok(false);
+        };
+
+        let mut item = self.doc.as_item_mut();
+        for part in parents {
+            match item.get_mut(part) {
+                Some(child) if child.is_table_like() => item = child,
+                _ => return Ok(false),
+            }
+        }
+        Ok(item.as_table_like_mut().and_then(|table| table.remove(last)).is_some())
+    }
+
+    /// Validates the document and writes it back.
+    pub fn save(&self) -> Result<()> {
+        self.validate()?;
+        write(&self.path, &self.doc.to_string())
+    }
+
+    fn validate(&self) -> Result<()> {
+        layers::check(&self.doc.to_string(), &self.path).map(|_| ())
+    }
+
+    fn with_value(&self, key: &str, value: Value) -> Result<Self> {
+        let mut copy = Self { path: self.path.clone(), doc: self.doc.clone() };
+        let parts: Vec<&str> = key.split('.').collect();
+        let (last, parents) = parts.split_last().expect("split always yields a part");
+
+        let mut table = copy.doc.as_table_mut();
+        for part in parents {
+            let item = table.entry(part).or_insert_with(|| {
+                let mut child = Table::new();
+                child.set_implicit(true);
+                Item::Table(child)
+            });
+            table = item.as_table_mut().ok_or_else(|| {
+                CodeRunnerError::ConfigError(format!("'{}' in '{}' is not a table", part, key))
+            })?;
+        }
+
+        // Keep the comment and spacing around an existing value.
+        match table.get_mut(last).and_then(Item::as_value_mut) {
+            Some(existing) => {
+                let decor = existing.decor().clone();
+                *existing = value;
+                *existing.decor_mut() = decor;
+            }
+            None => {
+                table.insert(last, Item::Value(value));
+            }
+        }
+        Ok(copy)
+    }
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> (tempfile::TempDir, ConfigFile) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, content).unwrap();
+        let file = ConfigFile::open(&path).unwrap();
+        (dir, file)
+    }
+
+    #[test]
+    fn test_set_preserves_comments() {
+        let (_dir, mut file) = file("# Execution timeout\ntimeout = 30 # seconds\n\n# Output\nsilent_mode = false\n");
+        file.set("timeout", "5").unwrap();
+        file.set("cwd", "file_dir").unwrap();
+        file.set("languages.python.runner", "pypy3").unwrap();
+        file.set("env.PORT", "8080").unwrap();
+        file.save().unwrap();
+
+        let content = fs::read_to_string(file.path()).unwrap();
+        assert!(content.starts_with("# Execution timeout\ntimeout = 5 # seconds\n\n# Output\n"), "{}", content);
+        assert!(content.contains("cwd = \"file_dir\""));
+        assert!(content.contains("[languages.python]\nrunner = \"pypy3\""));
+        assert!(!content.contains("[languages]\n"));
+        assert!(content.contains("PORT = \"8080\""));
+    }
+
+    #[test]
+    fn test_set_rejects_bad_keys_and_values() {
+        let (_dir, mut file) = file("");
+        assert!(file.set("timout", "5").unwrap_err().to_string().contains("did you mean 'timeout'"));
+        assert!(file.set("timeout", "soon").unwrap_err().to_string().contains("expected u64"));
+        assert!(file.set("languages.pyton.timeout", "5").is_err());
+        assert!(file.set("profiles.release.cobol", "[\"-O\"]").is_err());
+        assert!(file.set("lang", "python").is_err());
+        assert!(file.doc.to_string().is_empty());
+    }
+
+    #[test]
+    fn test_unset() {
+        let (_dir, mut file) = file("timeout = 5\n[languages.python]\ntimeout = 10\n");
+        assert!(file.unset("languages.python.timeout").unwrap());
+        assert!(!file.unset("languages.python.timeout").unwrap());
+        assert!(file.unset("timeout").unwrap());
+        assert!(!file.doc.to_string().contains("timeout"));
+    }
+
+    #[test]
+    fn test_template_is_valid() {
+        let (table, warnings) = layers::check(TEMPLATE, Path::new("template.toml")).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert!(table.contains_key("timeout"));
+    }
+}
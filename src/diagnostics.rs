@@ -0,0 +1,507 @@
+use crate::config::{MatcherPattern, ProblemMatcher};
+use crate::{CodeRunnerError, Result};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::fmt;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    /// Maps the words compilers use ("error", "fatal error", "warning",
+    /// "note", "help", ...) onto a severity.
+    pub fn parse(word: &str) -> Self {
+        let word = word.to_ascii_lowercase();
+        if word.contains("warn") {
+            Severity::Warning
+        } else if word.contains("note") || word.contains("help") || word.contains("info") {
+            Severity::Note
+        } else {
+            Severity::Error
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// A problem reported by a compiler or runtime, located in a source file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(file: &str, line: u32, column: Option<u32>, severity: Severity, message: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            line,
+            column,
+            severity,
+            message: message.trim().to_string(),
+        }
+    }
+}
+
+/// Renders as `path:line:col: severity: message`, which editors and
+/// terminals turn into a link to the location.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, ": {}: {}", self.severity, self.message)
+    }
+}
+
+/// Extracts diagnostics from a stage's combined output.
+pub type Parser = fn(&str) -> Vec<Diagnostic>;
+
+fn regex(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("built-in diagnostic pattern")
+}
+
+fn number(text: Option<regex::Match<'_>>) -> Option<u32> {
+    text.and_then(|m| m.as_str().parse().ok())
+}
+
+/// `file:line:col: error: message`, as written by gcc and clang.
+pub fn gcc(output: &str) -> Vec<Diagnostic> {
+    static PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+        regex(r"(?m)^(.+?):(\d+):(?:(\d+):)? (fatal error|error|warning|note): (.*)$")
+    });
+
+    PATTERN
+        .captures_iter(output)
+        .map(|c| {
+            Diagnostic::new(&c[1], number(c.get(2)).unwrap_or(0), number(c.get(3)), Severity::parse(&c[4]), &c[5])
+        })
+        .collect()
+}
+
+/// rustc's `error[E0425]: message` header followed by a ` --> file:line:col`
+/// location line.
+pub fn rustc(output: &str) -> Vec<Diagnostic> {
+    static PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+        regex(r"(?m)^(error|warning|note|help)(?:\[\w+\])?: (.*)\n\s*--> (.+?):(\d+):(\d+)$")
+    });
+
+    PATTERN
+        .captures_iter(output)
+        .map(|c| {
+            Diagnostic::new(&c[3], number(c.get(4)).unwrap_or(0), number(c.get(5)), Severity::parse(&c[1]), &c[2])
+        })
+        .collect()
+}
+
+/// `File.java:3: error: message`. javac marks the column with a caret two
+/// lines below, which is used when present.
+pub fn javac(output: &str) -> Vec<Diagnostic> {
+    static PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+        regex(r"(?m)^(.+?\.java):(\d+): (error|warning|note): (.*)$(?:\n.*\n( *)\^)?")
+    });
+
+    PATTERN
+        .captures_iter(output)
+        .map(|c| {
+            let column = c.get(5).map(|indent| indent.as_str().len() as u32 + 1);
+            Diagnostic::new(&c[1], number(c.get(2)).unwrap_or(0), column, Severity::parse(&c[3]), &c[4])
+        })
+        .collect()
+}
+
+/// Both of tsc's formats: `file.ts(3,5): error TS2322: message` and the
+/// pretty `file.ts:3:5 - error TS2322: message`.
+pub fn tsc(output: &str) -> Vec<Diagnostic> {
+    static PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+        regex(r"(?m)^(.+?)(?:\((\d+),(\d+)\):|:(\d+):(\d+) -) (error|warning|message) (TS\d+: .*)$")
+    });
+
+    PATTERN
+        .captures_iter(output)
+        .map(|c| {
+            let line = number(c.get(2).or(c.get(4))).unwrap_or(0);
+            let column = number(c.get(3).or(c.get(5)));
+            Diagnostic::new(&c[1], line, column, Severity::parse(&c[6]), &c[7])
+        })
+        .collect()
+}
+
+/// `./main.go:5:2: message` from the compiler, and the innermost frame of a
+/// `panic:` trace.
+pub fn go(output: &str) -> Vec<Diagnostic> {
+    static COMPILE: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^(\S+\.go):(\d+):(\d+): (.*)$"));
+    static PANIC: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^panic: (.*)$"));
+    static FRAME: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^\t(\S+\.go):(\d+)(?: \+0x[0-9a-f]+)?$"));
+
+    let mut diagnostics: Vec<Diagnostic> = COMPILE
+        .captures_iter(output)
+        .map(|c| Diagnostic::new(&c[1], number(c.get(2)).unwrap_or(0), number(c.get(3)), Severity::Error, &c[4]))
+        .collect();
+
+    if let Some(panic) = PANIC.captures(output) {
+        // The first frame outside the runtime is where the program panicked.
+        let frame = FRAME
+            .captures_iter(&output[panic.get(0).map_or(0, |m| m.end())..])
+            .find(|c| !c[1].contains("/runtime/"));
+        if let Some(frame) = frame {
+            let message = format!("panic: {}", &panic[1]);
+            diagnostics.push(Diagnostic::new(&frame[1], number(frame.get(2)).unwrap_or(0), None, Severity::Error, &message));
+        }
+    }
+
+    diagnostics
+}
+
+/// A traceback is reported at its innermost frame with the exception as the
+/// message. Python prints source lines dedented, so carets don't give a
+/// reliable column and none is reported.
+pub fn python(output: &str) -> Vec<Diagnostic> {
+    static FRAME: LazyLock<Regex> = LazyLock::new(|| regex(r#"(?m)^\s*File "(.+?)", line (\d+)"#));
+    static EXCEPTION: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^(\w+(?:\.\w+)*(?:Error|Exception|Warning|Interrupt|Exit)\b.*)$"));
+
+    let mut diagnostics = Vec::new();
+    // Syntax errors in the main script come without a traceback header, so the
+    // text before the first header is searched as well.
+    for block in output.split("Traceback (most recent call last):") {
+        let Some(frame) = FRAME.captures_iter(block).last() else {
+            continue;
+        };
+        let after_frame = &block[frame.get(0).map_or(0, |m| m.end())..];
+        let Some(exception) = EXCEPTION.captures(after_frame) else {
+            continue;
+        };
+
+        let severity = if exception[1].contains("Warning") { Severity::Warning } else { Severity::Error };
+        diagnostics.push(Diagnostic::new(&frame[1], number(frame.get(2)).unwrap_or(0), None, severity, &exception[1]));
+    }
+    diagnostics
+}
+
+/// Node prints the throwing location as `file:line` on the first line, then the
+/// error, then `at` frames carrying a column.
+pub fn node(output: &str) -> Vec<Diagnostic> {
+    static LOCATION: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^(/.+?|[A-Za-z]:\\.+?|file://.+?):(\d+)$"));
+    static ERROR: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^(\w*(?:Error|Exception)\b.*|Uncaught .*)$"));
+    static FRAME: LazyLock<Regex> = LazyLock::new(|| regex(r"(?m)^\s+at (?:.*\()?(.+?):(\d+):(\d+)\)?$"));
+
+    let Some(location) = LOCATION.captures(output) else {
+        return Vec::new();
+    };
+    let after = &output[location.get(0).map_or(0, |m| m.end())..];
+    let Some(error) = ERROR.captures(after) else {
+        return Vec::new();
+    };
+
+    let file = location[1].trim_start_matches("file://");
+    let line = number(location.get(2)).unwrap_or(0);
+    let column = FRAME
+        .captures_iter(after)
+        .find(|frame| frame[1].trim_start_matches("file://") == file && number(frame.get(2)) == Some(line))
+        .and_then(|frame| number(frame.get(3)));
+
+    vec![Diagnostic::new(file, line, column, Severity::Error, &error[1])]
+}
+
+/// Runs `parser` over a stage's stderr and stdout; some tools, like tsc,
+/// report problems on stdout.
+pub fn collect<P>(parser: P, stdout: &str, stderr: &str) -> Vec<Diagnostic>
+where
+    P: Fn(&str) -> Vec<Diagnostic>,
+{
+    let mut diagnostics = parser(stderr);
+    for diagnostic in parser(stdout) {
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+/// A compiled [`ProblemMatcher`] from the config.
+#[derive(Debug)]
+pub struct Matcher {
+    patterns: Vec<(Regex, MatcherPattern)>,
+    severity: Severity,
+}
+
+impl Matcher {
+    pub fn new(config: &ProblemMatcher) -> Result<Self> {
+        let invalid = |reason: String| CodeRunnerError::ConfigError(format!("Invalid problem matcher: {}", reason));
+
+        if config.pattern.is_empty() {
+            return Err(invalid("it needs at least one pattern".to_string()));
+        }
+
+        let mut patterns = Vec::with_capacity(config.pattern.len());
+        for pattern in &config.pattern {
+            let regex = Regex::new(&pattern.regexp).map_err(|e| invalid(e.to_string()))?;
+            let groups = [pattern.file, pattern.line, pattern.column, pattern.severity, pattern.message];
+            if let Some(group) = groups.into_iter().flatten().find(|&group| group >= regex.captures_len()) {
+                return Err(invalid(format!("'{}' has no capture group {}", pattern.regexp, group)));
+            }
+            patterns.push((regex, pattern.clone()));
+        }
+
+        for (field, provided) in [
+            ("file", config.pattern.iter().any(|p| p.file.is_some())),
+            ("line", config.pattern.iter().any(|p| p.line.is_some())),
+            ("message", config.pattern.iter().any(|p| p.message.is_some())),
+        ] {
+            if !provided {
+                return Err(invalid(format!("no pattern captures the {}", field)));
+            }
+        }
+
+        Ok(Self {
+            patterns,
+            severity: config.severity.as_deref().map_or(Severity::Error, Severity::parse),
+        })
+    }
+
+    pub fn parse(&self, output: &str) -> Vec<Diagnostic> {
+        let lines: Vec<&str> = output.lines().collect();
+        let mut diagnostics = Vec::new();
+        let mut i = 0;
+
+        while i < lines.len() {
+            i = self.match_at(&lines, i, &mut diagnostics).unwrap_or(i + 1);
+        }
+
+        diagnostics
+    }
+
+    /// Matches the patterns against the lines starting at `start`, returning
+    /// the index of the first line after the match.
+    fn match_at(&self, lines: &[&str], start: usize, diagnostics: &mut Vec<Diagnostic>) -> Option<usize> {
+        let ((last_regex, last), leading) = self.patterns.split_last()?;
+        let mut fields = Fields::default();
+        let mut i = start;
+
+        for (regex, pattern) in leading {
+            fields.fill(&regex.captures(lines.get(i)?)?, pattern);
+            i += 1;
+        }
+
+        let mut problem = fields.clone();
+        problem.fill(&last_regex.captures(lines.get(i)?)?, last);
+        diagnostics.extend(problem.into_diagnostic(self.severity));
+        i += 1;
+
+        while let Some(captures) = lines.get(i).filter(|_| last.repeat).and_then(|line| last_regex.captures(line)) {
+            let mut problem = fields.clone();
+            problem.fill(&captures, last);
+            diagnostics.extend(problem.into_diagnostic(self.severity));
+            i += 1;
+        }
+
+        Some(i)
+    }
+}
+
+/// Values captured so far while matching a multi-line problem.
+#[derive(Debug, Clone, Default)]
+struct Fields {
+    file: Option<String>,
+    line: Option<String>,
+    column: Option<String>,
+    severity: Option<String>,
+    message: Option<String>,
+}
+
+impl Fields {
+    fn fill(&mut self, captures: &Captures<'_>, pattern: &MatcherPattern) {
+        let group = |index: Option<usize>| index.and_then(|i| captures.get(i)).map(|m| m.as_str().to_string());
+        for (field, index) in [
+            (&mut self.file, pattern.file),
+            (&mut self.line, pattern.line),
+            (&mut self.column, pattern.column),
+            (&mut self.severity, pattern.severity),
+            (&mut self.message, pattern.message),
+        ] {
+            if let Some(value) = group(index) {
+                *field = Some(value);
+            }
+        }
+    }
+
+    fn into_diagnostic(self, default_severity: Severity) -> Option<Diagnostic> {
+        Some(Diagnostic::new(
+            &self.file?,
+            self.line?.parse().ok()?,
+            self.column.and_then(|column| column.parse().ok()),
+            self.severity.as_deref().map_or(default_severity, Severity::parse),
+            &self.message?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gcc() {
+        let output = "main.c: In function 'main':\n\
+                      main.c:4:5: error: 'x' undeclared (first use in this function)\n\
+                      main.c:7:1: warning: control reaches end of non-void function [-Wreturn-type]\n";
+        let diagnostics = gcc(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].to_string(), "main.c:4:5: error: 'x' undeclared (first use in this function)");
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_rustc() {
+        let output = "error[E0425]: cannot find value `x` in this scope\n --> src/main.rs:2:13\n  |\n";
+        assert_eq!(
+            rustc(output),
+            [Diagnostic::new("src/main.rs", 2, Some(13), Severity::Error, "cannot find value `x` in this scope")]
+        );
+    }
+
+    #[test]
+    fn test_javac_uses_caret_column() {
+        let output = "Main.java:3: error: ';' expected\n        int x = 1\n                 ^\n1 error\n";
+        let diagnostics = javac(output);
+        assert_eq!(diagnostics[0].to_string(), "Main.java:3:18: error: ';' expected");
+    }
+
+    #[test]
+    fn test_tsc_formats() {
+        let plain = tsc("app.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.\n");
+        let pretty = tsc("app.ts:3:7 - error TS2322: Type 'string' is not assignable to type 'number'.\n");
+        assert_eq!(plain, pretty);
+        assert_eq!(plain[0].column, Some(7));
+    }
+
+    #[test]
+    fn test_go_compile_error_and_panic() {
+        assert_eq!(go("# command-line-arguments\n./main.go:5:2: undefined: x\n")[0].line, 5);
+
+        let panic = "panic: runtime error: index out of range [5] with length 3\n\n\
+                     goroutine 1 [running]:\nmain.main()\n\t/tmp/main.go:7 +0x1d\nexit status 2\n";
+        let diagnostics = go(panic);
+        assert_eq!(diagnostics[0].file, "/tmp/main.go");
+        assert_eq!(diagnostics[0].line, 7);
+        assert!(diagnostics[0].message.starts_with("panic: runtime error"));
+    }
+
+    #[test]
+    fn test_python_traceback() {
+        let output = "Traceback (most recent call last):\n  \
+                      File \"/tmp/a.py\", line 5, in <module>\n    main()\n  \
+                      File \"/tmp/a.py\", line 2, in main\n    return 1 / 0\n           ~~^~~\n\
+                      ZeroDivisionError: division by zero\n";
+        assert_eq!(
+            python(output),
+            [Diagnostic::new("/tmp/a.py", 2, None, Severity::Error, "ZeroDivisionError: division by zero")]
+        );
+    }
+
+    #[test]
+    fn test_python_syntax_error() {
+        let output = "  File \"/tmp/a.py\", line 1\n    print(\"hi\"\n         ^\nSyntaxError: '(' was never closed\n";
+        let diagnostics = python(output);
+        assert_eq!(diagnostics[0].to_string(), "/tmp/a.py:1: error: SyntaxError: '(' was never closed");
+    }
+
+    #[test]
+    fn test_node_error() {
+        let output = "/tmp/a.js:2\n  foo();\n  ^\n\nReferenceError: foo is not defined\n    \
+                      at Object.<anonymous> (/tmp/a.js:2:3)\n    at Module._compile (node:internal/modules/cjs/loader:1256:14)\n";
+        assert_eq!(
+            node(output),
+            [Diagnostic::new("/tmp/a.js", 2, Some(3), Severity::Error, "ReferenceError: foo is not defined")]
+        );
+    }
+
+    fn pattern(regexp: &str) -> MatcherPattern {
+        MatcherPattern { regexp: regexp.to_string(), ..MatcherPattern::default() }
+    }
+
+    #[test]
+    fn test_single_line_matcher() {
+        let matcher = Matcher::new(&ProblemMatcher {
+            severity: Some("warning".to_string()),
+            pattern: vec![MatcherPattern {
+                file: Some(1),
+                line: Some(2),
+                column: Some(3),
+                message: Some(4),
+                ..pattern(r"^(\S+)\((\d+), (\d+)\) (.*)$")
+            }],
+            ..ProblemMatcher::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            matcher.parse("ok\nmain.nim(3, 7) type mismatch\n"),
+            [Diagnostic::new("main.nim", 3, Some(7), Severity::Warning, "type mismatch")]
+        );
+    }
+
+    #[test]
+    fn test_multi_line_matcher_with_loop() {
+        let matcher = Matcher::new(&ProblemMatcher {
+            pattern: vec![
+                MatcherPattern { file: Some(1), ..pattern(r"^In (\S+):$") },
+                MatcherPattern {
+                    line: Some(1),
+                    severity: Some(2),
+                    message: Some(3),
+                    repeat: true,
+                    ..pattern(r"^  (\d+): (\w+) (.*)$")
+                },
+            ],
+            ..ProblemMatcher::default()
+        })
+        .unwrap();
+
+        let output = "In rules.dsl:\n  4: error unknown rule\n  9: warning unused\ndone\n  12: error ignored\n";
+        let diagnostics = matcher.parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].to_string(), "rules.dsl:4: error: unknown rule");
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_invalid_matchers_are_rejected() {
+        let missing_group = ProblemMatcher {
+            pattern: vec![MatcherPattern { file: Some(1), line: Some(2), message: Some(3), ..pattern(r"^(.*):(\d+)$") }],
+            ..ProblemMatcher::default()
+        };
+        assert!(Matcher::new(&missing_group).unwrap_err().to_string().contains("no capture group 3"));
+
+        let missing_message = ProblemMatcher {
+            pattern: vec![MatcherPattern { file: Some(1), line: Some(2), ..pattern(r"^(.*):(\d+)$") }],
+            ..ProblemMatcher::default()
+        };
+        assert!(Matcher::new(&missing_message).unwrap_err().to_string().contains("message"));
+        assert!(Matcher::new(&ProblemMatcher { pattern: vec![pattern("(")], ..ProblemMatcher::default() }).is_err());
+    }
+
+    #[test]
+    fn test_plain_output_has_no_diagnostics() {
+        assert!(collect(gcc, "hello: world\n", "").is_empty());
+        assert!(python("Hello\n").is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! `code-runner:` directives in a source file's header comment, such as
+//!
+//! ```text
+//! // code-runner: args="--n 5" stdin=in.txt timeout=5 flags="-std=c++20 -lm"
+//! ```
+//!
+//! The header is the run of comments and blank lines at the top of the file.
+
+use crate::commands::Language;
+use crate::{CodeRunnerError, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+const MARKER: &str = "code-runner:";
+
+/// The longest timeout a file may ask for: a year, in seconds.
+const MAX_TIMEOUT: u64 = 365 * 24 * 60 * 60;
+
+/// Run settings declared by a source file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Directives {
+    /// Arguments passed to the program.
+    pub args: Vec<String>,
+    /// File fed to the program's stdin, relative to the source file.
+    pub stdin: Option<PathBuf>,
+    /// Timeout in seconds, overriding the configured one.
+    pub timeout: Option<u64>,
+    /// Extra compiler flags.
+    pub flags: Vec<String>,
+}
+
+impl Directives {
+    /// Reads the directives in the header of `path`. Files that aren't valid
+    /// UTF-8 have none.
+    pub fn read(path: &Path, language: &Language) -> Result<Self> {
+        let lines = BufReader::new(File::open(path)?).lines().map_while(|line| line.ok());
+        let mut directives = Self::parse(lines, language, path)?;
+
+        if let Some(stdin) = directives.stdin.take() {
+            let stdin = path.parent().map_or(stdin.clone(), |dir| dir.join(stdin));
+            if !stdin.is_file() {
+                return Err(CodeRunnerError::FileNotFound(stdin.display().to_string()));
+            }
+            directives.stdin = Some(stdin);
+        }
+        Ok(directives)
+    }
+
+    fn parse<I: IntoIterator<Item = String>>(lines: I, language: &Language, path: &Path) -> Result<Self> {
+        let mut directives = Self::default();
+
+        for (index, line) in lines.into_iter().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (index == 0 && line.starts_with("#!")) {
+                continue;
+            }
+            let Some(comment) = line.strip_prefix(language.comment) else {
+                break;
+            };
+            let comment = comment.trim_end();
+            let comment = comment.strip_suffix("*)").or_else(|| comment.strip_suffix("*/")).unwrap_or(comment);
+            let Some(settings) = comment.trim_start_matches(['/', '*', ' ', '\t']).strip_prefix(MARKER) else {
+                continue;
+            };
+
+            directives.apply(settings).map_err(|message| {
+                CodeRunnerError::InvalidDirective(format!("{}:{}: {}", path.display(), index + 1, message))
+            })?;
+        }
+        Ok(directives)
+    }
+
+    fn apply(&mut self, settings: &str) -> std::result::Result<(), String> {
+        for word in split_words(settings)? {
+            let (key, value) = word.split_once('=')
+                .ok_or_else(|| format!("expected key=value, found '{}'", word))?;
+            match key {
+                "args" => self.args.extend(split_words(value)?),
+                "flags" => self.flags.extend(split_words(value)?),
+                "stdin" => self.stdin = Some(PathBuf::from(value)),
+                "timeout" => {
+                    let timeout = value.parse()
+                        .map_err(|_| format!("timeout must be a whole number of seconds, found '{}'", value))?;
+                    if timeout > MAX_TIMEOUT {
+                        return Err(format!("timeout must be at most {} seconds, found {}", MAX_TIMEOUT, timeout));
+                    }
+                    self.timeout = Some(timeout);
+                }
+                _ => return Err(format!("unknown setting '{}' (expected args, stdin, timeout or flags)", key)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `text` on whitespace, keeping quoted runs together and dropping
+/// the quotes around them.
+fn split_words(text: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+
+    for c in text.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(q) = quote {
+        return Err(format!("unterminated {} quote", q));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::language_named;
+
+    fn parse(source: &str, language: &str) -> Result<Directives> {
+        let lines = source.lines().map(str::to_string);
+        Directives::parse(lines, language_named(language).unwrap(), Path::new("main.src"))
+    }
+
+    #[test]
+    fn test_header_directives() {
+        let source = "// code-runner: args=\"--n 5\" stdin=in.txt\n\
+                      // code-runner: timeout=5 flags=\"-std=c++20 -lm\"\n\
+                      #include <cstdio>\n\
+                      // code-runner: args=ignored\n";
+        let directives = parse(source, "cpp").unwrap();
+        assert_eq!(directives.args, ["--n", "5"]);
+        assert_eq!(directives.stdin, Some(PathBuf::from("in.txt")));
+        assert_eq!(directives.timeout, Some(5));
+        assert_eq!(directives.flags, ["-std=c++20", "-lm"]);
+    }
+
+    #[test]
+    fn test_comment_syntax() {
+        let python = parse("#!/usr/bin/env python3\n\n# Sums input\n# code-runner: args=\"'a b' c\"\n", "python");
+        assert_eq!(python.unwrap().args, ["a b", "c"]);
+        assert_eq!(parse("-- code-runner: timeout=2\n", "haskell").unwrap().timeout, Some(2));
+        assert_eq!(parse("(* code-runner: args=f(x) *)\n", "ocaml").unwrap().args, ["f(x)"]);
+        assert_eq!(parse("# code-runner: args=x\n", "c").unwrap(), Directives::default());
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        let error = parse("\n// code-runner: timeout=soon\n", "go").unwrap_err().to_string();
+        assert!(error.contains("main.src:2: timeout must be"), "{}", error);
+        let error = parse("// code-runner: timeout=18446744073709551615\n", "go").unwrap_err().to_string();
+        assert!(error.contains("timeout must be at most 31536000 seconds"), "{}", error);
+        assert!(parse("// code-runner: stdn=in.txt\n", "go").unwrap_err().to_string().contains("unknown setting 'stdn'"));
+        assert!(parse("// code-runner: args=\"oops\n", "go").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! `code-runner doctor`: checks the toolchains, config files and directories
+//! a run depends on, so a failure can be traced to its cause in one place.
+
+use crate::{commands, history, install, layers, toolchain, Config};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+use std::thread;
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+/// How long a `--version` probe may take; JVM tools are slow to start.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Tool {
+    pub program: String,
+    pub path: Option<PathBuf>,
+    /// First line of the program's version output.
+    pub version: Option<String>,
+    /// Languages that run the program.
+    pub languages: Vec<&'static str>,
+    /// How to install the program, when it is missing.
+    pub install: Vec<String>,
+}
+
+/// The result of checking one config file or directory.
+pub struct Check {
+    pub subject: String,
+    pub problem: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+pub struct Report {
+    pub tools: Vec<Tool>,
+    pub configs: Vec<Check>,
+    pub dirs: Vec<Check>,
+    /// Problems that involve several tools, such as mismatched JDKs.
+    pub conflicts: Vec<String>,
+}
+
+impl Report {
+    /// Number of problems found. Missing toolchains don't count, since few
+    /// people have every language installed.
+    pub fn problems(&self) -> usize {
+        let failed = |checks: &[Check]| checks.iter().filter(|check| check.problem.is_some()).count();
+        failed(&self.configs) + failed(&self.dirs) + self.conflicts.len()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::from("Toolchains\n");
+        let width = self.tools.iter().map(|tool| tool.program.len()).max().unwrap_or(0);
+        for tool in &self.tools {
+            let detail = match (&tool.path, &tool.version) {
+                (Some(path), Some(version)) => format!("{}  {}", path.display(), version),
+                (Some(path), None) => format!("{}  (version unknown)", path.display()),
+                (None, _) => match tool.install.first() {
+                    Some(hint) => format!("not found (needed by {}); install with: {}", tool.languages.join(", "), hint),
+                    None => format!("not found (needed by {})", tool.languages.join(", ")),
+                },
+            };
+            let mark = if tool.path.is_some() { "ok" } else { "--" };
+            let _ = writeln!(out, "  {:<4} {:<width$}  {}", mark, tool.program, detail);
+        }
+        for conflict in &self.conflicts {
+            let _ = writeln!(out, "  !!   {}", conflict);
+        }
+
+        for (title, checks) in [("Configuration", &self.configs), ("Directories", &self.dirs)] {
+            let _ = writeln!(out, "\n{}", title);
+            for check in checks {
+                match &check.problem {
+                    Some(problem) => { let _ = writeln!(out, "  !!   {}: {}", check.subject, problem); }
+                    None => { let _ = writeln!(out, "  ok   {}", check.subject); }
+                }
+                for warning in &check.warnings {
+                    let _ = writeln!(out, "       warning: {}", warning);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Checks everything `config` could need.
+pub fn run(config: &Config) -> Report {
+    let tools = tools(config);
+    let conflicts = java_mismatch(&tools).into_iter().collect();
+    let mut configs = configs();
+    configs.extend(unresolved(config));
+    Report { tools, configs, dirs: dirs(config), conflicts }
+}
+
+fn tools(config: &Config) -> Vec<Tool> {
+    let mut users: BTreeMap<String, Vec<&'static str>> = BTreeMap::new();
+    for language in commands::LANGUAGES {
+        for program in toolchain::programs(language, config).into_iter().flatten() {
+            users.entry(program).or_default().push(language.name);
+        }
+    }
+
+    // Probing one tool at a time would add up to minutes with JVM tools.
+    thread::scope(|scope| {
+        let probes: Vec<_> = users
+            .into_iter()
+            .map(|(program, languages)| {
+                scope.spawn(move || {
+                    let path = crate::utils::find_program(&program);
+                    let version = path.as_ref().and_then(|path| version(&program, path));
+                    let install = if path.is_none() { install::hints(&program) } else { Vec::new() };
+                    Tool { program, path, version, languages, install }
+                })
+            })
+            .collect();
+        let mut tools: Vec<Tool> = probes.into_iter().filter_map(|probe| probe.join().ok()).collect();
+        tools.sort_by_key(|tool| tool.program.to_lowercase());
+        tools
+    })
+}
+
+/// The arguments that make `program` print its version, if it can.
+fn version_args(program: &str) -> Option<&'static [&'static str]> {
+    match program {
+        "java" | "javac" | "kotlinc" | "scala" => Some(&["-version"]),
+        "go" => Some(&["version"]),
+        "rustc" => Some(&["-V"]),
+        "escript" => Some(&["-e", "io:format(\"~s~n\", [erlang:system_info(otp_release)])."]),
+        "sh" | "cmd" | "tclsh" => None,
+        _ => Some(&["--version"]),
+    }
+}
+
+/// Both pipes are drained while waiting, so a tool that writes a lot to one
+/// of them can't block on a full pipe until the timeout.
+fn version(program: &str, path: &Path) -> Option<String> {
+    let mut child = Command::new(path)
+        .args(version_args(program)?)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let drain = |mut pipe: Box<dyn Read + Send>| thread::spawn(move || {
+        let mut text = String::new();
+        let _ = pipe.read_to_string(&mut text);
+        text
+    });
+    let stdout = drain(Box::new(child.stdout.take()?));
+    let stderr = drain(Box::new(child.stderr.take()?));
+
+    // On a timeout the readers are left to finish once the pipes close.
+    if child.wait_timeout(VERSION_TIMEOUT).ok()?.is_none() {
+        let _ = child.kill();
+        let _ = child.wait();
+        return None;
+    }
+    let output = stdout.join().ok()? + &stderr.join().ok()?;
+    first_line(&output)
+}
+
+fn first_line(output: &str) -> Option<String> {
+    output.lines().map(str::trim).find(|line| !line.is_empty()).map(str::to_string)
+}
+
+/// Classes from a newer `javac` won't load in an older `java`, which shows
+/// up as an `UnsupportedClassVersionError` long after compiling succeeded.
+fn java_mismatch(tools: &[Tool]) -> Option<String> {
+    let major = |name: &str| {
+        let tool = tools.iter().find(|tool| tool.program == name)?;
+        Some((java_major(tool.version.as_deref()?)?, tool.path.clone()?))
+    };
+    let (javac, javac_path) = major("javac")?;
+    let (java, java_path) = major("java")?;
+
+    (javac != java).then(|| format!(
+        "javac {} ({}) and java {} ({}) are from different JDKs",
+        javac, javac_path.display(), java, java_path.display()
+    ))
+}
+
+/// The major version in `javac 17.0.2` or `openjdk version "1.8.0_292"`.
+fn java_major(version: &str) -> Option<u32> {
+    static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)(?:\.(\d+))?").unwrap());
+    let captures = NUMBER.captures(version)?;
+    let first: u32 = captures[1].parse().ok()?;
+    match captures.get(2) {
+        Some(second) if first == 1 => second.as_str().parse().ok(),
+        _ => Some(first),
+    }
+}
+
+fn configs() -> Vec<Check> {
+    let project = std::env::current_dir().ok().and_then(|dir| layers::find_project_config(&dir));
+    let mut checks: Vec<Check> = Config::config_path()
+        .filter(|path| path.is_file())
+        .into_iter()
+        .chain(project)
+        .map(|path| match layers::read_file(&path) {
+            Ok((_, warnings)) => Check { subject: path.display().to_string(), problem: None, warnings },
+            Err(e) => Check { subject: path.display().to_string(), problem: Some(e.to_string()), warnings: Vec::new() },
+        })
+        .collect();
+
+    // Environment variables and the merged result can fail even when every
+    // file is fine on its own.
+    let merged = match layers::load(None, &[]) {
+        Ok(loaded) => {
+            let seen: Vec<&String> = checks.iter().flat_map(|check| &check.warnings).collect();
+            let warnings = loaded.warnings.iter().filter(|warning| !seen.contains(warning)).cloned().collect();
+            Check { subject: "merged configuration".to_string(), problem: None, warnings }
+        }
+        Err(e) => Check { subject: "merged configuration".to_string(), problem: Some(e.to_string()), warnings: Vec::new() },
+    };
+    if checks.iter().all(|check| check.problem.is_none()) {
+        checks.push(merged);
+    }
+    checks
+}
+
+/// Languages whose programs can't be worked out from `config`.
+fn unresolved(config: &Config) -> Vec<Check> {
+    commands::LANGUAGES
+        .iter()
+        .filter_map(|language| {
+            let problem = toolchain::programs(language, config).err()?;
+            Some(Check {
+                subject: format!("languages.{}", language.name),
+                problem: Some(problem.to_string()),
+                warnings: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+fn dirs(config: &Config) -> Vec<Check> {
+    let mut dirs = vec![("build directory", Some(crate::cache::root()))];
+    if config.history {
+        dirs.push(("history directory", history::path().and_then(|path| path.parent().map(Path::to_path_buf))));
+    }
+
+    dirs.into_iter()
+        .map(|(name, dir)| match dir {
+            Some(dir) => Check {
+                subject: format!("{} {}", name, dir.display()),
+                problem: writable(&dir).err(),
+                warnings: Vec::new(),
+            },
+            None => Check { subject: name.to_string(), problem: Some("cannot be determined".to_string()), warnings: Vec::new() },
+        })
+        .collect()
+}
+
+/// Creates `dir` if needed and writes a scratch file to it.
+fn writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(format!(".code-runner-doctor-{}", std::process::id()));
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| format!("not writable ({})", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(program: &str, version: &str) -> Tool {
+        Tool {
+            program: program.to_string(),
+            path: Some(PathBuf::from(format!("/usr/bin/{}", program))),
+            version: Some(version.to_string()),
+            languages: vec!["java"],
+            install: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_java_major() {
+        assert_eq!(java_major("javac 17.0.2"), Some(17));
+        assert_eq!(java_major("openjdk version \"21\" 2023-09-19"), Some(21));
+        assert_eq!(java_major("java version \"1.8.0_292\""), Some(8));
+        assert_eq!(java_major("javac"), None);
+    }
+
+    #[test]
+    fn test_java_mismatch() {
+        let matching = [tool("javac", "javac 17.0.2"), tool("java", "openjdk version \"17.0.2\" 2022-01-18")];
+        assert!(java_mismatch(&matching).is_none());
+
+        let mismatched = [tool("javac", "javac 21.0.1"), tool("java", "openjdk version \"17.0.2\" 2022-01-18")];
+        let conflict = java_mismatch(&mismatched).unwrap();
+        assert!(conflict.contains("javac 21 (/usr/bin/javac) and java 17"), "{}", conflict);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_version_of_a_chatty_tool() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let tool = dir.path().join("chatty");
+        fs::write(&tool, "#!/bin/sh\nhead -c 200000 /dev/zero | tr '\\0' x >&2\necho \"chatty 1.0\"\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(version("chatty", &tool).as_deref(), Some("chatty 1.0"));
+        assert_eq!(version_args("dotnet"), Some(&["--version"][..]));
+    }
+
+    #[test]
+    fn test_writable() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(writable(&dir.path().join("nested")).is_ok());
+
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(writable(&file).unwrap_err().starts_with("not writable"));
+    }
+
+    #[test]
+    fn test_render() {
+        let report = Report {
+            tools: vec![
+                tool("javac", "javac 17.0.2"),
+                Tool { path: None, version: None, install: vec!["brew install go".to_string()], ..tool("go", "") },
+            ],
+            configs: vec![Check { subject: "config.toml".to_string(), problem: Some("config.toml:1:1: bad".to_string()), warnings: Vec::new() }],
+            dirs: Vec::new(),
+            conflicts: Vec::new(),
+        };
+
+        let text = report.render();
+        assert!(text.contains("  ok   javac  /usr/bin/javac  javac 17.0.2"), "{}", text);
+        assert!(text.contains("  --   go     not found (needed by java); install with: brew install go"), "{}", text);
+        assert!(text.contains("  !!   config.toml: config.toml:1:1: bad"));
+        assert_eq!(report.problems(), 1);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{CodeRunnerError, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Variables kept when the environment is cleared, so the program and the
+/// compilers behind it can still be found, and tools that look for a home
+/// directory, a locale or a terminal keep working.
+pub const PRESERVED_VARS: &[&str] = if cfg!(target_os = "windows") {
+    &["PATH", "SystemRoot", "TEMP", "TMP", "USERPROFILE", "USERNAME"]
+} else {
+    &["PATH", "HOME", "USER", "LANG", "TERM"]
+};
+
+/// The environment a program runs with, relative to code-runner's own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Environment {
+    pub vars: BTreeMap<String, String>,
+    pub clear: bool,
+}
+
+impl Environment {
+    pub fn merge_file(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path).map_err(|e| CodeRunnerError::ConfigError(
+            format!("Cannot read env file '{}': {}", path.display(), e)
+        ))?;
+        self.vars.extend(parse_dotenv(&content, &path.display().to_string())?);
+        Ok(())
+    }
+
+    pub fn apply(&self, command: &mut Command) {
+        if self.clear {
+            command.env_clear();
+            for name in PRESERVED_VARS {
+                if let Some(value) = std::env::var_os(name) {
+                    command.env(name, value);
+                }
+            }
+        }
+        command.envs(&self.vars);
+    }
+}
+
+/// Parses `.env` style content: `KEY=value` lines with optional `export`
+/// prefixes, `#` comments and single- or double-quoted values.
+pub fn parse_dotenv(content: &str, source: &str) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+
+    for (index, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (key, value) = line.split_once('=').ok_or_else(|| CodeRunnerError::ConfigError(
+            format!("{}:{}: expected KEY=VALUE", source, index + 1)
+        ))?;
+
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(CodeRunnerError::ConfigError(
+                format!("{}:{}: invalid variable name '{}'", source, index + 1, key)
+            ));
+        }
+
+        vars.insert(key.to_string(), unquote(value.trim()));
+    }
+
+    Ok(vars)
+}
+
+fn unquote(value: &str) -> String {
+    let quoted = |q: char| value.len() >= 2 && value.starts_with(q) && value.ends_with(q);
+
+    if quoted('\'') {
+        value[1..value.len() - 1].to_string()
+    } else if quoted('"') {
+        value[1..value.len() - 1]
+            .replace("\\n", "\n")
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\")
+    } else {
+        match value.find(" #") {
+            Some(pos) => value[..pos].trim_end().to_string(),
+            None => value.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let content = "\
+# comment
+RUST_BACKTRACE=1
+export PYTHONUNBUFFERED = 1
+GREETING=\"hello world\"
+RAW='a\\nb'
+TRAILING=value # note
+EMPTY=
+";
+        let vars = parse_dotenv(content, ".env").unwrap();
+        assert_eq!(vars["RUST_BACKTRACE"], "1");
+        assert_eq!(vars["PYTHONUNBUFFERED"], "1");
+        assert_eq!(vars["GREETING"], "hello world");
+        assert_eq!(vars["RAW"], "a\\nb");
+        assert_eq!(vars["TRAILING"], "value");
+        assert_eq!(vars["EMPTY"], "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clear_keeps_only_preserved_vars() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo \"[$CODE_RUNNER_TEST_LEAK]\"; echo \"[$HOME]\""]);
+        command.env("CODE_RUNNER_TEST_LEAK", "leaked").env("HOME", "/home/tester");
+        Environment { clear: true, ..Environment::default() }.apply(&mut command);
+
+        let output = String::from_utf8(command.output().unwrap().stdout).unwrap();
+        let home = std::env::var("HOME").unwrap_or_default();
+        assert_eq!(output, format!("[]\n[{}]\n", home));
+    }
+
+    #[test]
+    fn test_parse_dotenv_reports_line() {
+        let err = parse_dotenv("A=1\nnot a pair\n", "secrets.env").unwrap_err();
+        assert!(err.to_string().contains("secrets.env:2"));
+    }
+}
@@ -1,115 +1,729 @@
+use crate::commands::{self, Stage};
+use crate::diagnostics::{self, Diagnostic, Matcher};
+use crate::output::{self, OutputBuffer};
+use crate::process::{Exit, RunningProcess};
 use crate::{CodeRunnerError, CommandSpec, Config, Result};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 use std::io::{self, Write};
 use std::process::{Command, Stdio};
-use std::time::Duration;
-use wait_timeout::ChildExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
 
 pub struct Executor {
     config: Config,
+    cancel: Option<Arc<AtomicBool>>,
+    events: Option<EventSink>,
+    background: bool,
+}
+
+/// Progress notifications emitted while a run is in flight.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ExecutionEvent {
+    RunStarted { command: CommandSpec },
+    StageStarted { stage: usize, command: String },
+    Output { stage: usize, stream: Stream, data: String },
+    StageFinished { stage: usize, result: ExecutionResult },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+pub type EventSink = Arc<dyn Fn(ExecutionEvent) + Send + Sync>;
+
+enum Outcome {
+    Exited(Exit),
+    TimedOut,
+    OutputLimitExceeded,
+    Cancelled,
 }
 
 impl Executor {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self { config, cancel: None, events: None, background: false }
     }
     
+    /// Stops a running program as soon as `cancel` is raised.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Reports each stage and its output to `events` as it happens.
+    pub fn with_events(mut self, events: EventSink) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Runs programs without the terminal, so that several can run at once:
+    /// they read an empty stdin and are never made the foreground group.
+    pub fn in_background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+
+    pub fn emit(&self, event: ExecutionEvent) {
+        if let Some(events) = &self.events {
+            events(event);
+        }
+    }
+
     pub fn execute(&self, cmd_spec: &CommandSpec) -> Result<ExecutionResult> {
+        let mut record = self.record(cmd_spec);
+        match record.error {
+            Some(e) => Err(e),
+            None => Ok(record.results.pop().expect("a successful run has results")),
+        }
+    }
+
+    /// Runs every stage of `cmd_spec`, stopping at the first failure. Unlike
+    /// [`Executor::execute`], the results of a failed stage are kept.
+    pub fn record(&self, cmd_spec: &CommandSpec) -> RunRecord {
+        let mut record = RunRecord::default();
+
         if cmd_spec.program.is_empty() {
-            return Err(CodeRunnerError::ExecutionFailed(
+            record.error = Some(CodeRunnerError::ExecutionFailed(
                 "Program name is empty".to_string()
             ));
+            return record;
         }
-        
+
         if !self.config.silent_mode {
             println!("Running: {}\n", cmd_spec.display_string());
-            io::stdout().flush()?;
+            io::stdout().flush().ok();
+        }
+
+        for (index, stage) in cmd_spec.stages().iter().enumerate() {
+            match self.run_stage(index, stage, cmd_spec) {
+                Ok(result) => {
+                    let error = self.check(&result, cmd_spec).err();
+                    record.results.push(result);
+                    if error.is_some() {
+                        record.error = error;
+                        break;
+                    }
+                }
+                Err(e) => {
+                    record.error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        record
+    }
+
+    /// Runs the compile stage of `cmd_spec`, if it has one.
+    pub fn compile(&self, cmd_spec: &CommandSpec) -> Result<Option<ExecutionResult>> {
+        match &cmd_spec.compile {
+            Some(stage) => {
+                let result = self.run_stage(0, stage, cmd_spec)?;
+                self.check(&result, cmd_spec)?;
+                Ok(Some(result))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Runs the final stage of `cmd_spec`, assuming it has been compiled.
+    pub fn run(&self, cmd_spec: &CommandSpec) -> Result<ExecutionResult> {
+        let index = usize::from(cmd_spec.compile.is_some());
+        let result = self.run_stage(index, &cmd_spec.run_stage(), cmd_spec)?;
+        self.check(&result, cmd_spec)?;
+        Ok(result)
+    }
+
+    /// Turns an unsuccessful result into the matching error.
+    fn check(&self, result: &ExecutionResult, cmd_spec: &CommandSpec) -> Result<()> {
+        match result.termination {
+            Termination::TimedOut => Err(CodeRunnerError::Timeout(self.timeout(cmd_spec))),
+            Termination::Cancelled => Err(CodeRunnerError::Cancelled),
+            Termination::OutputLimitExceeded => {
+                Err(CodeRunnerError::OutputLimitExceeded(self.config.max_output_bytes))
+            }
+            Termination::Exited if !result.success => Err(CodeRunnerError::ExecutionFailed(
+                format!("Command exited with code: {}", result.exit_code)
+            )),
+            Termination::Exited => Ok(()),
         }
-        
-        let mut child = Command::new(&cmd_spec.program)
-            .args(&cmd_spec.args)
+    }
+
+    /// The timeout in seconds for `cmd_spec`, which the file itself may set.
+    fn timeout(&self, cmd_spec: &CommandSpec) -> u64 {
+        self.config.stage_timeout(&cmd_spec.language, cmd_spec.timeout)
+    }
+
+    fn run_stage(&self, index: usize, stage: &Stage, cmd_spec: &CommandSpec) -> Result<ExecutionResult> {
+        self.emit(ExecutionEvent::StageStarted { stage: index, command: stage.display_string() });
+        let result = self.spawn_stage(index, stage, cmd_spec)?;
+        self.emit(ExecutionEvent::StageFinished { stage: index, result: result.clone() });
+        Ok(result)
+    }
+
+    fn spawn_stage(&self, index: usize, stage: &Stage, cmd_spec: &CommandSpec) -> Result<ExecutionResult> {
+        let environment = self.config.environment(&cmd_spec.language)?;
+        let matchers = self.config.problem_matchers.iter()
+            .filter(|matcher| matcher.applies_to(&cmd_spec.language))
+            .map(Matcher::new)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut command = Command::new(resolve_program(&stage.program, cmd_spec.cwd.as_deref()));
+        command
+            .args(&stage.args)
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
+            .stderr(Stdio::piped());
+        environment.apply(&mut command);
+        if let Some(stdin) = cmd_spec.stdin.as_ref().filter(|_| index == usize::from(cmd_spec.compile.is_some())) {
+            command.stdin(fs::File::open(stdin)?);
+        } else if self.background {
+            command.stdin(Stdio::null());
+        }
+        if let Some(cwd) = &cmd_spec.cwd {
+            command.current_dir(cwd);
+        }
+
+        let spawned = if self.background {
+            RunningProcess::spawn_in_background(&mut command)
+        } else {
+            RunningProcess::spawn(&mut command)
+        };
+        let mut process = spawned
             .map_err(|e| CodeRunnerError::ExecutionFailed(
-                format!("Failed to start '{}': {}", cmd_spec.program, e)
+                format!("Failed to start '{}': {}", stage.program, e)
             ))?;
-        
-        let timeout = Duration::from_secs(self.config.timeout);
-        
-        match child.wait_timeout(timeout)? {
-            Some(status) => {
-                let output = child.wait_with_output()?;
-                
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = status.code().unwrap_or(-1);
-                
-                if !self.config.silent_mode {
-                    if !stderr.trim().is_empty() {
-                        eprintln!("stderr:\n{}", stderr);
-                    }
-                    if !stdout.is_empty() {
-                        print!("{}", stdout);
-                        io::stdout().flush()?;
-                    }
-                }
-                
-                if !status.success() {
-                    return Err(CodeRunnerError::ExecutionFailed(
-                        format!("Command exited with code: {}", exit_code)
-                    ));
+        let started = process.started();
+
+        let limit = self.config.max_output_bytes as usize;
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let stdout_reader = process.child_mut().stdout.take()
+            .map(|out| output::capture(out, limit, exceeded.clone(), self.stream_to_events(index, Stream::Stdout)));
+        let stderr_reader = process.child_mut().stderr.take()
+            .map(|err| output::capture(err, limit, exceeded.clone(), self.stream_to_events(index, Stream::Stderr)));
+
+        let timeout = Duration::from_secs(self.timeout(cmd_spec));
+        let outcome = self.wait(&mut process, &exceeded, timeout)?;
+
+        let stdout = join_reader(stdout_reader);
+        let stderr = join_reader(stderr_reader);
+        let truncated = stdout.is_truncated() || stderr.is_truncated();
+        let stdout = stdout.into_string();
+        let stderr = stderr.into_string();
+
+        let (termination, exit) = match outcome {
+            Outcome::Exited(exit) => (Termination::Exited, Some(exit)),
+            Outcome::TimedOut => (Termination::TimedOut, None),
+            Outcome::Cancelled => (Termination::Cancelled, None),
+            Outcome::OutputLimitExceeded => (Termination::OutputLimitExceeded, None),
+        };
+
+        let mut diagnostics = commands::language_named(&cmd_spec.language)
+            .and_then(|language| language.diagnostics)
+            .map(|parser| diagnostics::collect(parser, &stdout, &stderr))
+            .unwrap_or_default();
+        for matcher in &matchers {
+            for diagnostic in diagnostics::collect(|output| matcher.parse(output), &stdout, &stderr) {
+                if !diagnostics.contains(&diagnostic) {
+                    diagnostics.push(diagnostic);
                 }
-                
-                Ok(ExecutionResult {
-                    stdout,
-                    stderr,
-                    exit_code,
-                    success: true,
-                })
             }
-            None => {
-                child.kill()?;
-                child.wait()?;
-                Err(CodeRunnerError::Timeout(self.config.timeout))
+        }
+
+        if termination != Termination::TimedOut && termination != Termination::Cancelled {
+            self.print_output(&stdout, &stderr)?;
+            self.print_diagnostics(&diagnostics);
+        }
+
+        Ok(ExecutionResult {
+            command: stage.display_string(),
+            stdout,
+            stderr,
+            exit_code: exit.and_then(|exit| exit.status.code()).unwrap_or(-1),
+            signal: exit.and_then(|exit| signal(exit.status)),
+            success: exit.is_some_and(|exit| exit.status.success()),
+            truncated,
+            wall_time: exit.map_or_else(|| started.elapsed(), |exit| exit.wall_time),
+            cpu_time: exit.and_then(|exit| exit.usage).map(|usage| usage.cpu_time),
+            peak_memory_kb: exit.and_then(|exit| exit.usage).map(|usage| usage.peak_memory_kb),
+            termination,
+            diagnostics,
+        })
+    }
+
+    fn stream_to_events(&self, index: usize, stream: Stream) -> Option<output::ChunkCallback> {
+        let events = self.events.clone()?;
+        Some(Box::new(move |data: &str| {
+            events(ExecutionEvent::Output { stage: index, stream, data: data.to_string() });
+        }))
+    }
+
+    fn wait(&self, process: &mut RunningProcess, exceeded: &AtomicBool, timeout: Duration) -> Result<Outcome> {
+        // A timeout too long to represent never expires.
+        let deadline = process.started().checked_add(timeout);
+
+        loop {
+            if let Some(exit) = process.wait_timeout(POLL_INTERVAL)? {
+                return Ok(Outcome::Exited(exit));
+            }
+
+            if self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::SeqCst)) {
+                process.kill_tree()?;
+                return Ok(Outcome::Cancelled);
+            }
+
+            if self.config.kill_on_output_limit && exceeded.load(Ordering::SeqCst) {
+                process.kill_tree()?;
+                return Ok(Outcome::OutputLimitExceeded);
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                process.kill_tree()?;
+                return Ok(Outcome::TimedOut);
             }
         }
     }
-    
-    pub fn cleanup(&self, extension: &str) -> Result<()> {
+
+    fn print_output(&self, stdout: &str, stderr: &str) -> Result<()> {
+        if !self.config.silent_mode {
+            if !stderr.trim().is_empty() {
+                eprintln!("stderr:\n{}", stderr);
+            }
+            if !stdout.is_empty() {
+                print!("{}", stdout);
+                io::stdout().flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists diagnostics as `path:line:col` locations an editor can jump to.
+    fn print_diagnostics(&self, diagnostics: &[Diagnostic]) {
+        if self.config.silent_mode || diagnostics.is_empty() {
+            return;
+        }
+        eprintln!("\nDiagnostics:");
+        for diagnostic in diagnostics {
+            eprintln!("  {}", diagnostic);
+        }
+    }
+
+    /// Removes the build directory the compile stage of `cmd_spec` wrote to.
+    /// With cleanup turned off, the directory is kept for `code-runner cache`.
+    pub fn cleanup(&self, cmd_spec: &CommandSpec) -> Result<()> {
         if !self.config.cleanup_artifacts {
+            if let Some(guard) = &cmd_spec.build_guard {
+                guard.keep();
+            }
             return Ok(());
         }
-        
-        match extension {
-            "rs" | "c" | "cpp" | "cc" | "cxx" => {
-                let main_exe = if cfg!(target_os = "windows") {
-                    "main.exe"
-                } else {
-                    "main"
-                };
-                
-                if Path::new(main_exe).exists() {
-                    fs::remove_file(main_exe).ok();
-                }
-            }
-            "nim" => {
-                if Path::new("main").exists() {
-                    fs::remove_file("main").ok();
-                }
+
+        if let Some(build_dir) = &cmd_spec.build_dir {
+            if build_dir.exists() {
+                fs::remove_dir_all(build_dir)?;
             }
-            _ => {}
         }
-        
+
         Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
+#[cfg(unix)]
+fn signal(status: std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_status: std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Relative paths like `./main` name an artifact in the run's working
+/// directory, not ours.
+fn resolve_program(program: &str, cwd: Option<&Path>) -> PathBuf {
+    let path = Path::new(program);
+    match cwd {
+        Some(cwd) if path.is_relative() && path.components().count() > 1 => cwd.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn join_reader(reader: Option<JoinHandle<OutputBuffer>>) -> OutputBuffer {
+    reader
+        .and_then(|handle| handle.join().ok())
+        .unwrap_or_else(|| OutputBuffer::new(0))
+}
+
+/// How a stage's process came to an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    Exited,
+    TimedOut,
+    OutputLimitExceeded,
+    Cancelled,
+}
+
+/// Overall verdict for a run, as shown in summaries and reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Passed,
+    Failed,
+    TimedOut,
+    OutputLimitExceeded,
+    Cancelled,
+}
+
+impl RunStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RunStatus::Passed => "ok",
+            RunStatus::Failed => "failed",
+            RunStatus::TimedOut => "timeout",
+            RunStatus::OutputLimitExceeded => "output limit",
+            RunStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// The stages that ran for one file and the error that stopped them, if any.
+#[derive(Debug, Default)]
+pub struct RunRecord {
+    pub results: Vec<ExecutionResult>,
+    pub error: Option<CodeRunnerError>,
+}
+
+impl RunRecord {
+    pub fn failed(error: CodeRunnerError) -> Self {
+        Self { results: Vec::new(), error: Some(error) }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn status(&self) -> RunStatus {
+        match &self.error {
+            None => RunStatus::Passed,
+            Some(CodeRunnerError::Timeout(_)) => RunStatus::TimedOut,
+            Some(CodeRunnerError::OutputLimitExceeded(_)) => RunStatus::OutputLimitExceeded,
+            Some(CodeRunnerError::Cancelled) => RunStatus::Cancelled,
+            Some(_) => RunStatus::Failed,
+        }
+    }
+
+    pub fn termination(&self) -> Option<Termination> {
+        self.results.last().map(|result| result.termination)
+    }
+
+    /// Combined wall time of every stage that ran.
+    pub fn wall_time(&self) -> Duration {
+        self.results.iter().map(|result| result.wall_time).sum()
+    }
+}
+
+impl Serialize for RunRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("RunRecord", 4)?;
+        state.serialize_field("status", &self.status())?;
+        state.serialize_field("wall_time_ms", &duration_ms::to_ms(self.wall_time()))?;
+        state.serialize_field("stages", &self.results)?;
+        state.serialize_field("error", &self.error)?;
+        state.end()
+    }
+}
+
+/// Durations are reported as fractional milliseconds.
+mod duration_ms {
+    use serde::Serializer;
+    use std::time::Duration;
+
+    pub fn to_ms(duration: Duration) -> f64 {
+        duration.as_secs_f64() * 1000.0
+    }
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(to_ms(*duration))
+    }
+
+    pub mod option {
+        use serde::Serializer;
+        use std::time::Duration;
+
+        pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+            match duration {
+                Some(duration) => serializer.serialize_some(&super::to_ms(*duration)),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ExecutionResult {
+    /// The stage's command line, as shown in "Running: ...".
+    pub command: String,
     pub stdout: String,
     pub stderr: String,
     pub exit_code: i32,
+    /// Signal that terminated the process, on Unix.
+    pub signal: Option<i32>,
     pub success: bool,
-}
\ No newline at end of file
+    pub truncated: bool,
+    #[serde(rename = "wall_time_ms", with = "duration_ms")]
+    pub wall_time: Duration,
+    #[serde(rename = "cpu_time_ms", with = "duration_ms::option")]
+    pub cpu_time: Option<Duration>,
+    pub peak_memory_kb: Option<u64>,
+    pub termination: Termination,
+    /// Problems parsed from the output by the language's diagnostics parser.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn quiet_config() -> Config {
+        Config {
+            silent_mode: true,
+            ..Config::default()
+        }
+    }
+
+    fn sh(script: &str) -> CommandSpec {
+        CommandSpec::new("sh".to_string(), vec!["-c".to_string(), script.to_string()])
+    }
+
+    #[test]
+    fn test_large_output_is_truncated() {
+        let config = Config {
+            max_output_bytes: 1024,
+            ..quiet_config()
+        };
+        let result = Executor::new(config)
+            .execute(&sh("head -c 200000 /dev/zero | tr '\\0' x"))
+            .unwrap();
+        assert!(result.truncated);
+        assert!(result.stdout.contains("output truncated"));
+        assert!(result.stdout.len() < 2048);
+    }
+
+    #[test]
+    fn test_runaway_output_is_killed() {
+        let config = Config {
+            max_output_bytes: 1024,
+            kill_on_output_limit: true,
+            ..quiet_config()
+        };
+        let err = Executor::new(config).execute(&sh("yes")).unwrap_err();
+        assert!(matches!(err, CodeRunnerError::OutputLimitExceeded(1024)));
+    }
+
+    #[test]
+    fn test_env_is_applied() {
+        let mut config = quiet_config();
+        config.env.insert("GREETING".to_string(), "global".to_string());
+        config.languages.insert("shell".to_string(), crate::config::LanguageConfig {
+            env: [("TARGET".to_string(), "lang".to_string())].into(),
+            ..Default::default()
+        });
+
+        let spec = sh("echo $GREETING $TARGET").with_language("shell");
+        let result = Executor::new(config).execute(&spec).unwrap();
+        assert_eq!(result.stdout.trim(), "global lang");
+    }
+
+    #[test]
+    fn test_clear_env_keeps_path() {
+        let config = Config {
+            clear_env: true,
+            ..quiet_config()
+        };
+        // cargo sets CARGO_MANIFEST_DIR for the tests, but not in the cleared environment.
+        let result = Executor::new(config)
+            .execute(&sh("echo \"[$CARGO_MANIFEST_DIR]\"; command -v sh"))
+            .unwrap();
+        let lines: Vec<&str> = result.stdout.lines().collect();
+        assert_eq!(lines[0], "[]");
+        assert!(lines[1].starts_with('/') && lines[1].ends_with("/sh"), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_runs_in_cwd() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let spec = sh("pwd").with_cwd(Some(dir.path().to_path_buf()));
+        let result = Executor::new(quiet_config()).execute(&spec).unwrap();
+        let expected = dir.path().canonicalize().unwrap();
+        assert_eq!(Path::new(result.stdout.trim()).canonicalize().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_cancel_stops_program() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let executor = Executor::new(quiet_config()).with_cancel(cancel.clone());
+
+        let flag = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        let err = executor.execute(&sh("sleep 30")).unwrap_err();
+        assert!(matches!(err, CodeRunnerError::Cancelled));
+    }
+
+    #[test]
+    fn test_compile_stage_runs_first() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let spec = CommandSpec::compiled(
+            Stage::new("sh".to_string(), vec!["-c".to_string(), "printf '#!/bin/sh\\necho built\\n' > main; chmod +x main".to_string()]),
+            "./main".to_string(),
+            vec![],
+        ).with_cwd(Some(dir.path().to_path_buf()));
+
+        let result = Executor::new(quiet_config()).execute(&spec).unwrap();
+        assert_eq!(result.stdout.trim(), "built");
+    }
+
+    #[test]
+    fn test_record_keeps_failed_stage() {
+        let record = Executor::new(quiet_config()).record(&sh("echo oops >&2; exit 3"));
+        assert!(!record.is_success());
+        assert_eq!(record.results.len(), 1);
+        assert_eq!(record.results[0].exit_code, 3);
+        assert_eq!(record.results[0].stderr.trim(), "oops");
+    }
+
+    #[test]
+    fn test_cleanup_removes_build_dir() {
+        let spec_in = |build_dir: &Arc<crate::utils::BuildDirGuard>| CommandSpec::compiled(
+            Stage::new("touch".to_string(), vec![build_dir.path().join("main").display().to_string()]),
+            "true".to_string(),
+            vec![],
+        ).with_build_dir(build_dir.clone());
+
+        let build_dir = crate::utils::BuildDirGuard::create().unwrap();
+        let path = build_dir.path().to_path_buf();
+        let spec = spec_in(&build_dir);
+        let executor = Executor::new(quiet_config());
+        executor.execute(&spec).unwrap();
+        assert!(path.join("main").exists());
+        executor.cleanup(&spec).unwrap();
+        assert!(!path.exists());
+
+        let build_dir = crate::utils::BuildDirGuard::create().unwrap();
+        let path = build_dir.path().to_path_buf();
+        let spec = spec_in(&build_dir);
+        Executor::new(Config { cleanup_artifacts: false, ..quiet_config() }).cleanup(&spec).unwrap();
+        drop((spec, build_dir));
+        assert!(path.exists());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_dir_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let build_dir = crate::utils::BuildDirGuard::create().unwrap();
+        let path = build_dir.path().to_path_buf();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        drop(build_dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_records_resource_usage() {
+        let result = Executor::new(quiet_config()).execute(&sh("sleep 0.1")).unwrap();
+        assert!(result.wall_time >= Duration::from_millis(100));
+        assert!(result.cpu_time.is_some());
+        assert!(result.peak_memory_kb.unwrap() > 0);
+    }
+
+    #[test]
+    fn test_language_timeout_overrides_global() {
+        let mut config = quiet_config();
+        config.languages.insert("shell".to_string(), crate::config::LanguageConfig {
+            timeout: Some(1),
+            ..Default::default()
+        });
+        let spec = sh("sleep 5").with_language("shell");
+
+        let started = Instant::now();
+        let error = Executor::new(config).execute(&spec).unwrap_err();
+        assert!(matches!(error, CodeRunnerError::Timeout(1)));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_huge_timeout_does_not_overflow() {
+        let config = Config { timeout: u64::MAX, ..quiet_config() };
+        assert!(Executor::new(config).execute(&sh("true")).is_ok());
+    }
+
+    #[test]
+    fn test_events_are_emitted() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let executor = Executor::new(quiet_config())
+            .with_events(Arc::new(move |event| sink.lock().unwrap().push(event)));
+
+        executor.execute(&sh("echo streamed")).unwrap();
+
+        let events = events.lock().unwrap();
+        assert!(matches!(events.first(), Some(ExecutionEvent::StageStarted { stage: 0, .. })));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ExecutionEvent::Output { stream: Stream::Stdout, data, .. } if data == "streamed\n"
+        )));
+        assert!(matches!(events.last(), Some(ExecutionEvent::StageFinished { .. })));
+    }
+
+    #[test]
+    fn test_result_serializes() {
+        let result = Executor::new(quiet_config()).execute(&sh("kill -9 $$")).unwrap_err();
+        assert!(matches!(result, CodeRunnerError::ExecutionFailed(_)));
+
+        let record = Executor::new(quiet_config()).record(&sh("kill -9 $$"));
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["stages"][0]["signal"], 9);
+        assert_eq!(json["stages"][0]["termination"], "exited");
+        assert_eq!(json["error"]["kind"], "execution_failed");
+    }
+
+    #[test]
+    fn test_background_programs_read_empty_stdin() {
+        let config = Config {
+            timeout: 5,
+            ..quiet_config()
+        };
+        let result = Executor::new(config).in_background().execute(&sh("cat; echo end")).unwrap();
+        assert_eq!(result.stdout, "end\n");
+    }
+
+    #[test]
+    fn test_timeout_kills_grandchildren() {
+        let config = Config {
+            timeout: 1,
+            ..quiet_config()
+        };
+        let start = Instant::now();
+        let err = Executor::new(config).execute(&sh("sleep 30; echo done")).unwrap_err();
+        assert!(matches!(err, CodeRunnerError::Timeout(1)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,182 @@
+//! A log of past runs for `code-runner history`, kept as one JSON object per
+//! line in the user data dir.
+
+use crate::executor::{RunRecord, RunStatus};
+use crate::{CodeRunnerError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of entries kept when the log is trimmed.
+pub const MAX_ENTRIES: usize = 1000;
+
+/// The log is trimmed once it grows past this size, a few thousand entries.
+const TRIM_BYTES: u64 = 512 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub file: String,
+    pub language: Option<String>,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub wall_time_ms: f64,
+}
+
+impl Entry {
+    pub fn new(file: &str, language: Option<&str>, record: &RunRecord) -> Self {
+        Self {
+            time: now(),
+            file: file.to_string(),
+            language: language.map(str::to_string),
+            status: record.status(),
+            exit_code: record.results.last().map(|result| result.exit_code),
+            wall_time_ms: record.wall_time().as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// `~/.local/share/code-runner/history.jsonl` on Linux.
+pub fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("code-runner").join("history.jsonl"))
+}
+
+/// Appends `entry` to the log at `path`. The line goes out in one write to a
+/// file opened for appending, so concurrent runs don't interleave. Once the
+/// log passes [`TRIM_BYTES`] it is cut back to the newest [`MAX_ENTRIES`].
+pub fn record(path: &Path, entry: &Entry) -> Result<()> {
+    let mut line = serde_json::to_string(entry).map_err(|e| CodeRunnerError::ExecutionFailed(e.to_string()))?;
+    line.push('\n');
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+
+    if file.metadata()?.len() > TRIM_BYTES {
+        trim(path, MAX_ENTRIES)?;
+    }
+    Ok(())
+}
+
+/// Keeps only the last `keep` lines of the log, replacing it by renaming a
+/// new file over it so readers never see it half written. A run recorded
+/// while this happens may be lost.
+fn trim(path: &Path, keep: usize) -> Result<()> {
+    let content = fs::read_to_string(path)?;
+    let lines = content.lines().collect::<Vec<_>>();
+    let skip = lines.len().saturating_sub(keep);
+
+    let mut file = tempfile::NamedTempFile::new_in(path.parent().unwrap_or(Path::new(".")))?;
+    for line in &lines[skip..] {
+        writeln!(file, "{}", line)?;
+    }
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Every entry in the log at `path`, oldest first. Lines that don't parse,
+/// say from a newer version, are skipped.
+pub fn load(path: &Path) -> Result<Vec<Entry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Deletes the log, returning whether there was one.
+pub fn clear(path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(path)?;
+    Ok(true)
+}
+
+/// One line per entry, newest last.
+pub fn render(entries: &[Entry]) -> String {
+    let now = now();
+    let mut out = String::new();
+    for entry in entries {
+        let _ = writeln!(
+            out,
+            "{:>8}  {:<12} {:>9.1}ms  {}",
+            age(now.saturating_sub(entry.time)),
+            entry.status.label(),
+            entry.wall_time_ms,
+            entry.file,
+        );
+    }
+    out
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str) -> Entry {
+        Entry::new(file, Some("python"), &RunRecord::default())
+    }
+
+    #[test]
+    fn test_record_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("data").join("history.jsonl");
+        assert!(load(&path).unwrap().is_empty());
+
+        record(&path, &entry("a.py")).unwrap();
+        record(&path, &entry("b.py")).unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "not json\n").unwrap();
+
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.file.as_str()).collect::<Vec<_>>(), ["a.py", "b.py"]);
+        assert_eq!(entries[0].status, RunStatus::Passed);
+        assert!(render(&entries).contains("just now  ok"));
+
+        assert!(clear(&path).unwrap());
+        assert!(!clear(&path).unwrap());
+    }
+
+    #[test]
+    fn test_trim_keeps_newest() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        for file in ["a.py", "b.py", "c.py"] {
+            record(&path, &entry(file)).unwrap();
+        }
+
+        trim(&path, 2).unwrap();
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.file.as_str()).collect::<Vec<_>>(), ["b.py", "c.py"]);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_age() {
+        assert_eq!(age(5), "just now");
+        assert_eq!(age(150), "2m ago");
+        assert_eq!(age(7200), "2h ago");
+        assert_eq!(age(3 * 86400), "3d ago");
+    }
+}
@@ -0,0 +1,139 @@
+//! Install hints for missing programs, for the package and version managers
+//! found on this machine.
+
+use crate::commands::{self, Program};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Installer {
+    Apt,
+    Dnf,
+    Pacman,
+    Apk,
+    Brew,
+    Nix,
+    Rustup,
+    Sdkman,
+    Nvm,
+    Pyenv,
+}
+
+/// One way to install a program: an installer and what to ask it for.
+#[derive(Debug, Clone, Copy)]
+pub struct Install(pub Installer, pub &'static str);
+
+impl Installer {
+    /// Version managers are listed first, since they are usually what the
+    /// user set up for that language on purpose.
+    const ALL: &'static [Installer] = &[
+        Installer::Rustup,
+        Installer::Sdkman,
+        Installer::Nvm,
+        Installer::Pyenv,
+        Installer::Brew,
+        Installer::Nix,
+        Installer::Apt,
+        Installer::Dnf,
+        Installer::Pacman,
+        Installer::Apk,
+    ];
+
+    /// The command that installs `package` with this installer.
+    pub fn command(self, package: &str) -> String {
+        match self {
+            Installer::Apt => format!("sudo apt install {}", package),
+            Installer::Dnf => format!("sudo dnf install {}", package),
+            Installer::Pacman => format!("sudo pacman -S {}", package),
+            Installer::Apk => format!("sudo apk add {}", package),
+            Installer::Brew => format!("brew install {}", package),
+            Installer::Nix => format!("nix profile install nixpkgs#{}", package),
+            Installer::Rustup => format!("rustup {}", package),
+            Installer::Sdkman => format!("sdk install {}", package),
+            Installer::Nvm => format!("nvm install {}", package),
+            Installer::Pyenv => format!("pyenv install {}", package),
+        }
+    }
+
+    /// Whether this installer is available here. sdkman and nvm are shell
+    /// functions, so they are found by their install directories instead.
+    pub fn detected(self) -> bool {
+        let found = |program: &str| crate::utils::find_program(program).is_some();
+        let dir = |var: &str, default: &str| {
+            std::env::var_os(var)
+                .map(PathBuf::from)
+                .or_else(|| dirs::home_dir().map(|home| home.join(default)))
+                .is_some_and(|dir| dir.is_dir())
+        };
+
+        match self {
+            Installer::Apt => found("apt-get"),
+            Installer::Dnf => found("dnf"),
+            Installer::Pacman => found("pacman"),
+            Installer::Apk => found("apk"),
+            Installer::Brew => found("brew"),
+            Installer::Nix => found("nix"),
+            Installer::Rustup => found("rustup"),
+            Installer::Sdkman => dir("SDKMAN_DIR", ".sdkman"),
+            Installer::Nvm => dir("NVM_DIR", ".nvm"),
+            Installer::Pyenv => found("pyenv"),
+        }
+    }
+}
+
+/// Commands that would install `program` with the installers found here,
+/// best first. Programs the registry doesn't know get none.
+pub fn hints(program: &str) -> Vec<String> {
+    match find(program) {
+        Some(program) => hints_for(program, |installer| installer.detected()),
+        None => Vec::new(),
+    }
+}
+
+fn hints_for(program: &Program, detected: impl Fn(Installer) -> bool) -> Vec<String> {
+    Installer::ALL
+        .iter()
+        .filter(|installer| detected(**installer))
+        .filter_map(|installer| {
+            program.install
+                .iter()
+                .find(|Install(candidate, _)| candidate == installer)
+                .map(|Install(installer, package)| installer.command(package))
+        })
+        .collect()
+}
+
+fn find(name: &str) -> Option<&'static Program> {
+    commands::LANGUAGES
+        .iter()
+        .flat_map(|language| language.programs)
+        .find(|program| program.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints_prefer_version_managers() {
+        let rustc = find("rustc").unwrap();
+        let hints = hints_for(rustc, |installer| matches!(installer, Installer::Apt | Installer::Rustup));
+        assert_eq!(hints, ["rustup default stable", "sudo apt install rustc"]);
+    }
+
+    #[test]
+    fn test_hints_for_detected_installers_only() {
+        let javac = find("javac").unwrap();
+        assert_eq!(hints_for(javac, |installer| installer == Installer::Dnf), ["sudo dnf install java-latest-openjdk-devel"]);
+        assert!(hints_for(javac, |_| false).is_empty());
+        assert!(hints("clang-from-nowhere").is_empty());
+    }
+
+    #[test]
+    fn test_every_program_has_a_hint() {
+        for language in commands::LANGUAGES {
+            for program in language.programs {
+                assert!(!program.install.is_empty(), "{} has no install hints", program.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,646 @@
+//! Builds the effective [`Config`] from several layers, each overriding the
+//! ones before it: built-in defaults, the user config file, the nearest
+//! `.code-runner.toml`, `CODE_RUNNER_*` environment variables and CLI flags.
+
+use crate::config::{CwdPolicy, LanguageConfig, MatcherPattern, ProblemMatcher};
+use crate::{CodeRunnerError, Config, Result};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+pub const PROJECT_FILE: &str = ".code-runner.toml";
+pub const ENV_PREFIX: &str = "CODE_RUNNER_";
+
+/// Settings a project file can't change, since they guard against the files
+/// it sits next to.
+const USER_ONLY: &[&str] = &["require_trust", "trusted_roots", "follow_symlinks", "allowed_roots"];
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    User(PathBuf),
+    Project(PathBuf),
+    Env(String),
+    Cli(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::User(path) => write!(f, "user config {}", path.display()),
+            Origin::Project(path) => write!(f, "project config {}", path.display()),
+            Origin::Env(var) => write!(f, "environment {}", var),
+            Origin::Cli(flag) => write!(f, "command line {}", flag),
+        }
+    }
+}
+
+/// A setting given on the command line, e.g. `--cwd` for `cwd`.
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub flag: String,
+    pub key: String,
+    pub value: Value,
+}
+
+impl Override {
+    pub fn new(flag: &str, key: &str, value: impl Into<Value>) -> Self {
+        Self {
+            flag: flag.to_string(),
+            key: key.to_string(),
+            value: value.into(),
+        }
+    }
+}
+
+/// The merged configuration, with the origin of every setting in it.
+#[derive(Debug)]
+pub struct LoadedConfig {
+    pub config: Config,
+    /// Origins keyed by dotted setting name, e.g. `languages.python.env`.
+    pub origins: BTreeMap<String, Origin>,
+    /// Problems that did not stop loading, such as unknown keys.
+    pub warnings: Vec<String>,
+    merged: Table,
+}
+
+impl LoadedConfig {
+    /// The effective value of a dotted setting name.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        let mut parts = key.split('.').peekable();
+        let mut table = &self.merged;
+        while let Some(part) = parts.next() {
+            let value = table.get(part)?;
+            if parts.peek().is_none() {
+                return Some(value);
+            }
+            table = value.as_table()?;
+        }
+        None
+    }
+
+    /// Every setting as `key = value`, optionally annotated with its origin.
+    pub fn render(&self, with_origin: bool) -> String {
+        let mut entries = Vec::new();
+        flatten(&self.merged, "", &mut entries);
+
+        let width = entries.iter().map(|(key, value)| key.len() + value.len() + 3).max().unwrap_or(0);
+        let mut out = String::new();
+        for (key, value) in entries {
+            let line = format!("{} = {}", key, value);
+            match self.origins.get(&key).filter(|_| with_origin) {
+                Some(origin) => {
+                    let _ = writeln!(out, "{:<width$}  # {}", line, origin, width = width);
+                }
+                None => {
+                    let _ = writeln!(out, "{}", line);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// The built-in defaults alone, without reading any file or the environment.
+pub fn defaults() -> Result<LoadedConfig> {
+    Builder::new()?.finish()
+}
+
+/// Loads the configuration that applies to `source`. The project file is
+/// searched for from the file's directory, or from the current directory when
+/// there is no file.
+pub fn load(source: Option<&Path>, overrides: &[Override]) -> Result<LoadedConfig> {
+    let mut builder = Builder::new()?;
+
+    if let Some(path) = Config::config_path().filter(|path| path.is_file()) {
+        builder.merge_file(&path, Origin::User(path.clone()))?;
+    }
+
+    let start = match source {
+        Some(file) => file.parent().map(Path::to_path_buf).unwrap_or_default(),
+        None => std::env::current_dir()?,
+    };
+    let project = find_project_config(&start);
+    if let Some(path) = &project {
+        builder.merge_file(path, Origin::Project(path.clone()))?;
+    }
+
+    builder.merge_env(std::env::vars());
+
+    for item in overrides {
+        let path: Vec<&str> = item.key.split('.').collect();
+        builder.set(&path, item.value.clone(), &Origin::Cli(item.flag.clone()));
+    }
+
+    let loaded = builder.finish()?;
+    if let Some(path) = project {
+        crate::trust::check(&fs::canonicalize(&path).unwrap_or(path), &loaded.config)?;
+    }
+    Ok(loaded)
+}
+
+/// Walks up from `start` to the nearest directory containing a project file.
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Parses a config file, checking it against the [`Config`] schema. Errors
+/// point at `path:line:column`; unknown keys become warnings.
+pub fn read_file(path: &Path) -> Result<(Table, Vec<String>)> {
+    check(&fs::read_to_string(path)?, path)
+}
+
+/// Checks `content` as if it were read from `path`; see [`read_file`].
+pub fn check(content: &str, path: &Path) -> Result<(Table, Vec<String>)> {
+    let located = |span: Option<std::ops::Range<usize>>, message: &str| {
+        let (line, column) = span.map_or((1, 1), |span| line_column(content, span.start));
+        CodeRunnerError::ConfigError(format!("{}:{}:{}: {}", path.display(), line, column, message.trim().replace('\n', ", ")))
+    };
+
+    let table: Table = toml::from_str(content).map_err(|e| located(e.span(), e.message()))?;
+
+    let mut unknown = Vec::new();
+    let checked: std::result::Result<Config, _> =
+        serde_ignored::deserialize(toml::Deserializer::new(content), |key| unknown.push(key.to_string()));
+    checked.map_err(|e| located(e.span(), e.message()))?;
+
+    let warnings = unknown
+        .iter()
+        .map(|key| {
+            let location = find_key_line(content, key)
+                .map_or_else(|| path.display().to_string(), |line| format!("{}:{}", path.display(), line));
+            match suggest(key) {
+                Some(suggestion) => format!("{}: unknown key '{}', did you mean '{}'?", location, key, suggestion),
+                None => format!("{}: unknown key '{}'", location, key),
+            }
+        })
+        .collect();
+
+    Ok((table, warnings))
+}
+
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |last| last.chars().count()) + 1;
+    (line, column)
+}
+
+/// The line an unknown key is written on, as `key =` or inside a table header.
+fn find_key_line(content: &str, key: &str) -> Option<usize> {
+    let name = key.rsplit('.').next()?;
+    content
+        .lines()
+        .position(|line| {
+            let line = line.trim_start();
+            let assigned = line
+                .strip_prefix(name)
+                .or_else(|| line.strip_prefix(&format!("\"{}\"", name)))
+                .is_some_and(|rest| rest.trim_start().starts_with('='));
+            let header = line.starts_with('[') && line.split(['[', ']', '.']).any(|part| part.trim() == name);
+            assigned || header
+        })
+        .map(|index| index + 1)
+}
+
+/// Checks that `key` names a setting, as a dotted path like `timeout`,
+/// `env.RUST_LOG` or `languages.python.runner`.
+pub fn check_key(key: &str) -> Result<()> {
+    let parts: Vec<&str> = key.split('.').collect();
+    let known = match parts.as_slice() {
+        [name] => field_names::<Config>().contains(name),
+        ["env", _] | ["languages", _, "env", _] => true,
+        ["profiles", _, language] => {
+            if crate::commands::language_named(language).is_none() {
+                return Err(CodeRunnerError::ConfigError(format!("Unknown language '{}'", language)));
+            }
+            true
+        }
+        ["languages", language, name] => {
+            if crate::commands::language_named(language).is_none() {
+                return Err(CodeRunnerError::ConfigError(format!("Unknown language '{}'", language)));
+            }
+            field_names::<LanguageConfig>().contains(name)
+        }
+        _ => false,
+    };
+
+    if known {
+        return Ok(());
+    }
+    Err(CodeRunnerError::ConfigError(match suggest(key) {
+        Some(suggestion) => format!("Unknown key '{}', did you mean '{}'?", key, suggestion),
+        None => format!("Unknown key '{}'", key),
+    }))
+}
+
+/// The closest known key at the same level as `key`, if one is close enough
+/// to be a likely typo.
+fn suggest(key: &str) -> Option<&'static str> {
+    let parts: Vec<&str> = key.split('.').collect();
+    let (name, parents) = parts.split_last()?;
+    let candidates = match parents {
+        [] => field_names::<Config>(),
+        ["languages", _] => field_names::<LanguageConfig>(),
+        ["problem_matchers", _] => field_names::<ProblemMatcher>(),
+        ["problem_matchers", _, "pattern", _] => field_names::<MatcherPattern>(),
+        _ => &[],
+    };
+
+    candidates
+        .iter()
+        .map(|candidate| (strsim::damerau_levenshtein(name, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The field names a struct's `Deserialize` impl accepts, captured by handing
+/// it a deserializer that only records what it is asked for.
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct Fields(&'static [&'static str]);
+
+    impl de::Error for Fields {
+        fn custom<E: fmt::Display>(_: E) -> Self {
+            Fields(&[])
+        }
+    }
+
+    impl std::error::Error for Fields {}
+
+    impl fmt::Debug for Fields {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_list().entries(self.0).finish()
+        }
+    }
+
+    impl fmt::Display for Fields {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    struct Introspect;
+
+    impl<'de> Deserializer<'de> for Introspect {
+        type Error = Fields;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> std::result::Result<V::Value, Fields> {
+            Err(Fields(&[]))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> std::result::Result<V::Value, Fields> {
+            Err(Fields(fields))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    match T::deserialize(Introspect) {
+        Err(Fields(fields)) => fields,
+        Ok(_) => &[],
+    }
+}
+
+struct Builder {
+    merged: Table,
+    origins: BTreeMap<String, Origin>,
+    warnings: Vec<String>,
+}
+
+impl Builder {
+    fn new() -> Result<Self> {
+        let defaults = Table::try_from(Config::default())
+            .map_err(|e| CodeRunnerError::ConfigError(e.to_string()))?;
+        let mut builder = Self { merged: Table::new(), origins: BTreeMap::new(), warnings: Vec::new() };
+        builder.merge(defaults, &Origin::Default);
+        Ok(builder)
+    }
+
+    fn merge_file(&mut self, path: &Path, origin: Origin) -> Result<()> {
+        let (mut table, mut warnings) = read_file(path)?;
+        if let Some(dir) = path.parent() {
+            rebase_paths(&mut table, dir);
+            if let Some(Value::Table(languages)) = table.get_mut("languages") {
+                for (_, language) in languages.iter_mut() {
+                    if let Value::Table(language) = language {
+                        rebase_paths(language, dir);
+                    }
+                }
+            }
+        }
+        if matches!(origin, Origin::Project(_)) {
+            for key in USER_ONLY {
+                if table.remove(*key).is_some() {
+                    warnings.push(format!("{}: '{}' can only be set in the user config", path.display(), key));
+                }
+            }
+        }
+        self.merge(table, &origin);
+        self.warnings.append(&mut warnings);
+        Ok(())
+    }
+
+    /// `CODE_RUNNER_TIMEOUT=5` sets `timeout`; a double underscore reaches
+    /// into tables, as in `CODE_RUNNER_LANGUAGES__PYTHON__CLEAR_ENV=true`.
+    fn merge_env(&mut self, vars: impl Iterator<Item = (String, String)>) {
+        let mut vars: Vec<_> = vars.filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+        vars.sort();
+
+        for (name, raw) in vars {
+            let key = name[ENV_PREFIX.len()..].to_ascii_lowercase();
+            let path: Vec<&str> = key.split("__").filter(|part| !part.is_empty()).collect();
+            if path.is_empty() {
+                continue;
+            }
+            let value = parse_env_value(&raw, self.get(&path));
+            self.set(&path, value, &Origin::Env(name.clone()));
+        }
+    }
+
+    fn merge(&mut self, table: Table, origin: &Origin) {
+        for (key, value) in table {
+            self.set(&[key.as_str()], value, origin);
+        }
+    }
+
+    fn get(&self, path: &[&str]) -> Option<&Value> {
+        let (last, parents) = path.split_last()?;
+        let mut table = &self.merged;
+        for part in parents {
+            table = table.get(*part)?.as_table()?;
+        }
+        table.get(*last)
+    }
+
+    /// Sets the value at `path`, merging tables key by key so a layer only
+    /// replaces the settings it mentions.
+    fn set(&mut self, path: &[&str], value: Value, origin: &Origin) {
+        let Some((last, parents)) = path.split_last() else {
+            return;
+        };
+
+        if let (Value::Table(incoming), Some(Value::Table(_))) = (&value, self.get(path)) {
+            for (key, child) in incoming.clone() {
+                let mut child_path = path.to_vec();
+                child_path.push(&key);
+                self.set(&child_path, child, origin);
+            }
+            return;
+        }
+
+        let prefix = path.join(".");
+        self.origins.retain(|key, _| key != &prefix && !key.starts_with(&format!("{}.", prefix)));
+        record_origins(&prefix, &value, origin, &mut self.origins);
+        self.table_at(parents).insert(last.to_string(), value);
+    }
+
+    /// The table at `path`, creating it (and replacing non-tables) as needed.
+    fn table_at(&mut self, path: &[&str]) -> &mut Table {
+        let mut table = &mut self.merged;
+        for part in path {
+            let entry = table.entry(part.to_string()).or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            table = entry.as_table_mut().expect("just made a table");
+        }
+        table
+    }
+
+    /// Builds the config, naming the setting and its origin if a value from
+    /// the environment or command line has the wrong type.
+    fn finish(self) -> Result<LoadedConfig> {
+        let config = serde_path_to_error::deserialize(Value::Table(self.merged.clone())).map_err(|e| {
+            let key = e.path().to_string();
+            let message = e.inner().message().trim().to_string();
+            match self.origins.get(&key) {
+                Some(origin) => CodeRunnerError::ConfigError(format!("{} (from {}): {}", key, origin, message)),
+                None => CodeRunnerError::ConfigError(format!("{}: {}", key, message)),
+            }
+        })?;
+
+        Ok(LoadedConfig {
+            config,
+            origins: self.origins,
+            warnings: self.warnings,
+            merged: self.merged,
+        })
+    }
+}
+
+/// Makes the relative `env_file` and `cwd` paths in a file's `table` relative
+/// to `dir`, the file's directory, rather than to wherever we were started.
+fn rebase_paths(table: &mut Table, dir: &Path) {
+    for key in ["env_file", "cwd"] {
+        let Some(Value::String(value)) = table.get_mut(key) else {
+            continue;
+        };
+        if key == "cwd" && !matches!(CwdPolicy::from(value.clone()), CwdPolicy::Path(_)) {
+            continue;
+        }
+        if Path::new(value.as_str()).is_relative() {
+            *value = dir.join(&*value).display().to_string();
+        }
+    }
+}
+
+fn record_origins(key: &str, value: &Value, origin: &Origin, origins: &mut BTreeMap<String, Origin>) {
+    match value {
+        Value::Table(table) if !table.is_empty() => {
+            for (child, value) in table {
+                record_origins(&format!("{}.{}", key, child), value, origin, origins);
+            }
+        }
+        _ => {
+            origins.insert(key.to_string(), origin.clone());
+        }
+    }
+}
+
+/// Environment variables are strings; they are read as TOML values unless the
+/// setting they replace is itself a string.
+fn parse_env_value(raw: &str, existing: Option<&Value>) -> Value {
+    if matches!(existing, Some(Value::String(_))) {
+        return Value::String(raw.to_string());
+    }
+
+    format!("value = {}", raw)
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+fn flatten(table: &Table, prefix: &str, entries: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::Table(child) if !child.is_empty() => flatten(child, &key, entries),
+            value => entries.push((key, value.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> Builder {
+        Builder::new().unwrap()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut builder = builder();
+        builder.merge("timeout = 5\n[env]\nA = \"1\"".parse().unwrap(), &Origin::User(PathBuf::from("user.toml")));
+        builder.merge("[env]\nB = \"2\"".parse().unwrap(), &Origin::Project(PathBuf::from(".code-runner.toml")));
+        builder.merge_env([("CODE_RUNNER_TIMEOUT".to_string(), "9".to_string())].into_iter());
+        builder.set(&["silent_mode"], Value::Boolean(true), &Origin::Cli("--silent".to_string()));
+
+        let loaded = builder.finish().unwrap();
+        assert_eq!(loaded.config.timeout, 9);
+        assert!(loaded.config.silent_mode);
+        assert_eq!(loaded.config.env.len(), 2);
+        assert_eq!(loaded.origins["timeout"], Origin::Env("CODE_RUNNER_TIMEOUT".to_string()));
+        assert_eq!(loaded.origins["env.A"], Origin::User(PathBuf::from("user.toml")));
+        assert_eq!(loaded.origins["env.B"], Origin::Project(PathBuf::from(".code-runner.toml")));
+        assert_eq!(loaded.origins["cleanup_artifacts"], Origin::Default);
+    }
+
+    #[test]
+    fn test_env_values_keep_string_settings() {
+        let mut builder = builder();
+        builder.merge_env(
+            [
+                ("CODE_RUNNER_CWD".to_string(), "1234".to_string()),
+                ("CODE_RUNNER_LANGUAGES__PYTHON__CLEAR_ENV".to_string(), "true".to_string()),
+                ("UNRELATED".to_string(), "x".to_string()),
+            ]
+            .into_iter(),
+        );
+
+        let loaded = builder.finish().unwrap();
+        assert_eq!(loaded.config.cwd.to_string(), "1234");
+        assert_eq!(loaded.config.languages["python"].clear_env, Some(true));
+    }
+
+    #[test]
+    fn test_render_with_origin() {
+        let mut builder = builder();
+        builder.set(&["timeout"], Value::Integer(3), &Origin::Cli("--timeout".to_string()));
+        let rendered = builder.finish().unwrap().render(true);
+
+        let line = rendered.lines().find(|line| line.starts_with("timeout = 3")).unwrap();
+        assert!(line.ends_with("# command line --timeout"));
+        assert!(rendered.lines().any(|line| line.starts_with("silent_mode = false") && line.ends_with("# default")));
+    }
+
+    fn write_config(content: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_FILE);
+        fs::write(&path, content).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_parse_error_has_location() {
+        let (_dir, path) = write_config("timeout = 5\nsilent_mode = yes\n");
+        let error = read_file(&path).unwrap_err().to_string();
+        assert!(error.contains(&format!("{}:2:15:", path.display())), "{}", error);
+    }
+
+    #[test]
+    fn test_type_error_has_location() {
+        let (_dir, path) = write_config("\ntimeout = \"five\"\n");
+        let error = read_file(&path).unwrap_err().to_string();
+        assert!(error.contains(&format!("{}:2:11:", path.display())), "{}", error);
+        assert!(error.contains("expected u64"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_keys_suggest_fixes() {
+        let (_dir, path) = write_config("timout = 5\n\n[languages.python]\nclear_evn = true\n\n[other]\nx = 1\n");
+        let (_, warnings) = read_file(&path).unwrap();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].ends_with(":1: unknown key 'timout', did you mean 'timeout'?"), "{:?}", warnings);
+        assert!(warnings[1].ends_with(":4: unknown key 'languages.python.clear_evn', did you mean 'clear_env'?"));
+        assert!(warnings[2].ends_with(":6: unknown key 'other'"));
+    }
+
+    #[test]
+    fn test_lang_is_not_a_setting() {
+        let (_dir, path) = write_config("lang = \"python\"\n");
+        let mut builder = builder();
+        builder.merge_file(&path, Origin::Project(path.clone())).unwrap();
+        let loaded = builder.finish().unwrap();
+        assert_eq!(loaded.config.lang, None);
+        assert!(loaded.warnings[0].contains("unknown key 'lang'"), "{:?}", loaded.warnings);
+    }
+
+    #[test]
+    fn test_bad_env_value_names_its_origin() {
+        let mut builder = builder();
+        builder.merge_env([("CODE_RUNNER_TIMEOUT".to_string(), "soon".to_string())].into_iter());
+        let error = builder.finish().unwrap_err().to_string();
+        assert!(error.contains("timeout (from environment CODE_RUNNER_TIMEOUT)"), "{}", error);
+    }
+
+    #[test]
+    fn test_project_config_is_found_upwards() {
+        let root = tempfile::TempDir::new().unwrap();
+        let nested = root.path().join("src/bin");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.path().join(PROJECT_FILE), "timeout = 1").unwrap();
+
+        assert_eq!(find_project_config(&nested), Some(root.path().join(PROJECT_FILE)));
+    }
+
+    #[test]
+    fn test_relative_paths_follow_the_file() {
+        let (dir, path) = write_config(
+            "env_file = \".env\"\ncwd = \"data\"\n[languages.python]\ncwd = \"file_dir\"\nenv_file = \"/etc/env\"\n\
+             [languages.go]\ncwd = \"../go\"\n",
+        );
+        let mut builder = builder();
+        builder.merge_file(&path, Origin::Project(path.clone())).unwrap();
+
+        let config = builder.finish().unwrap().config;
+        assert_eq!(config.env_file, Some(dir.path().join(".env")));
+        assert_eq!(config.cwd, CwdPolicy::Path(dir.path().join("data")));
+        assert_eq!(config.languages["python"].cwd, Some(CwdPolicy::FileDir));
+        assert_eq!(config.languages["python"].env_file, Some(PathBuf::from("/etc/env")));
+        assert_eq!(config.languages["go"].cwd, Some(CwdPolicy::Path(dir.path().join("../go"))));
+    }
+
+    #[test]
+    fn test_project_config_cannot_grant_trust() {
+        let (_dir, path) = write_config("require_trust = false\ntrusted_roots = [\"/\"]\ntimeout = 4\n");
+        let mut builder = builder();
+        builder.set(&["require_trust"], Value::Boolean(true), &Origin::User(PathBuf::from("user.toml")));
+        builder.merge_file(&path, Origin::Project(path.clone())).unwrap();
+
+        let loaded = builder.finish().unwrap();
+        assert!(loaded.config.require_trust);
+        assert!(loaded.config.trusted_roots.is_empty());
+        assert_eq!(loaded.config.timeout, 4);
+        assert_eq!(loaded.warnings.len(), 2);
+        assert!(loaded.warnings[0].ends_with("'require_trust' can only be set in the user config"), "{:?}", loaded.warnings);
+    }
+}
@@ -1,8 +1,27 @@
+pub mod batch;
+pub mod bench;
+pub mod cache;
 pub mod commands;
 pub mod config;
+pub mod config_file;
+pub mod diagnostics;
+pub mod directives;
+pub mod doctor;
+pub mod env;
 pub mod executor;
+pub mod history;
+pub mod install;
+pub mod layers;
+pub mod output;
+pub mod pipeline;
+pub mod plan;
+pub mod process;
+pub mod report;
+pub mod toolchain;
+pub mod trust;
 pub mod utils;
 pub mod validator;
+pub mod watch;
 
 pub use commands::CommandSpec;
 pub use config::Config;
@@ -10,6 +29,8 @@ pub use executor::Executor;
 pub use utils::FileInfo;
 pub use validator::Validator;
 
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -26,9 +47,15 @@ pub enum CodeRunnerError {
     #[error("File is a directory: {0}")]
     IsDirectory(String),
     
-    #[error("File is too large: {0} MB (max: {1} MB)")]
+    #[error("File is too large: {0} bytes (max: {1} bytes)")]
     FileTooLarge(u64, u64),
     
+    #[error("Binary file: {0} doesn't look like source code")]
+    BinaryFile(String),
+    
+    #[error("Unsupported encoding: {0}")]
+    UnsupportedEncoding(String),
+    
     #[error("Program not installed: {0}")]
     ProgramNotInstalled(String),
     
@@ -38,11 +65,60 @@ pub enum CodeRunnerError {
     #[error("Timeout: Command exceeded {0} seconds")]
     Timeout(u64),
     
+    #[error("Cancelled")]
+    Cancelled,
+    
+    #[error("Output Limit Exceeded: program wrote more than {0} bytes")]
+    OutputLimitExceeded(u64),
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     
     #[error("Configuration error: {0}")]
     ConfigError(String),
+    
+    #[error("Invalid directive: {0}")]
+    InvalidDirective(String),
+    
+    #[error("Untrusted file: {0}")]
+    Untrusted(String),
+    
+    #[error("doctor found {0} problem(s)")]
+    DoctorFindings(usize),
+}
+
+impl CodeRunnerError {
+    /// Stable identifier for the error, used in machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CodeRunnerError::FileNotFound(_) => "file_not_found",
+            CodeRunnerError::InvalidPath(_) => "invalid_path",
+            CodeRunnerError::UnsupportedFileType(_) => "unsupported_file_type",
+            CodeRunnerError::IsDirectory(_) => "is_directory",
+            CodeRunnerError::FileTooLarge(_, _) => "file_too_large",
+            CodeRunnerError::BinaryFile(_) => "binary_file",
+            CodeRunnerError::UnsupportedEncoding(_) => "unsupported_encoding",
+            CodeRunnerError::ProgramNotInstalled(_) => "program_not_installed",
+            CodeRunnerError::ExecutionFailed(_) => "execution_failed",
+            CodeRunnerError::Timeout(_) => "timeout",
+            CodeRunnerError::Cancelled => "cancelled",
+            CodeRunnerError::OutputLimitExceeded(_) => "output_limit_exceeded",
+            CodeRunnerError::IoError(_) => "io_error",
+            CodeRunnerError::ConfigError(_) => "config_error",
+            CodeRunnerError::InvalidDirective(_) => "invalid_directive",
+            CodeRunnerError::Untrusted(_) => "untrusted",
+            CodeRunnerError::DoctorFindings(_) => "doctor_findings",
+        }
+    }
+}
+
+impl Serialize for CodeRunnerError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CodeRunnerError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
 }
 
 pub type Result<T> = std::result::Result<T, CodeRunnerError>;
@@ -50,9 +126,6 @@ pub type Result<T> = std::result::Result<T, CodeRunnerError>;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
 
     #[test]
     fn test_file_info_extraction() {
@@ -78,6 +151,23 @@ mod tests {
         assert_eq!(info.ext, "");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_find_program() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let tool = dir.path().join("tool");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        let tool_path = tool.to_str().unwrap();
+        assert!(utils::find_program(tool_path).is_none());
+
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(utils::find_program(tool_path), Some(tool.clone()));
+        assert!(utils::find_program("sh").is_some());
+        assert!(utils::find_program("surely-not-an-installed-program").is_none());
+    }
+
     #[test]
     fn test_validator_empty_path() {
         let validator = Validator::new(100);
@@ -98,6 +188,101 @@ mod tests {
         assert!(validator.validate("-help").is_err());
     }
 
+    fn validate_content(name: &str, content: &[u8]) -> Result<Vec<String>> {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        Validator::new(1).validate_file(&utils::get_file_info(path.to_str().unwrap()))
+    }
+
+    #[test]
+    fn test_validator_size_is_byte_accurate() {
+        assert!(validate_content("limit.py", &vec![b'#'; 1024 * 1024]).is_ok());
+        assert!(matches!(
+            validate_content("over.py", &vec![b'#'; 1024 * 1024 + 1]),
+            Err(CodeRunnerError::FileTooLarge(1048577, 1048576))
+        ));
+    }
+
+    #[test]
+    fn test_validator_rejects_binary_and_wide_files() {
+        assert!(matches!(validate_content("a.py", b"\x7fELF\x02\x01\x01\0\0"), Err(CodeRunnerError::BinaryFile(_))));
+
+        let utf16: Vec<u8> = "print(1)\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let with_bom = [&[0xFF, 0xFE][..], &utf16].concat();
+        for content in [&utf16, &with_bom] {
+            let error = validate_content("a.py", content).unwrap_err();
+            assert!(matches!(&error, CodeRunnerError::UnsupportedEncoding(message) if message.contains("UTF-16LE")), "{}", error);
+        }
+
+        // PowerShell and .NET read the byte order mark, but need one.
+        assert!(validate_content("a.ps1", &with_bom).unwrap().is_empty());
+        assert!(validate_content("a.cs", &with_bom).unwrap().is_empty());
+        assert!(validate_content("a.ps1", &utf16).is_err());
+    }
+
+    #[test]
+    fn test_validator_rejects_crlf_in_shell_scripts() {
+        let error = validate_content("a.sh", b"echo hi\r\necho there\r\n").unwrap_err();
+        assert!(error.to_string().contains("CRLF"), "{}", error);
+        assert!(validate_content("a.py", b"print(1)\r\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validator_warnings() {
+        let warnings = validate_content("a.py", b"\xEF\xBB\xBFprint(1)\n").unwrap();
+        assert!(warnings[0].contains("UTF-8 byte order mark"), "{:?}", warnings);
+
+        let warnings = validate_content("a.c", b"/* caf\xE9 */\n").unwrap();
+        assert!(warnings[0].contains("not valid UTF-8"), "{:?}", warnings);
+        assert!(validate_content("a.c", "/* café */\n".as_bytes()).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validator_symlink_policy() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("real.py");
+        let link = dir.path().join("link.py");
+        std::fs::write(&target, "print(1)\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let file_info = utils::get_file_info(link.to_str().unwrap());
+
+        assert!(Validator::new(1).validate_file(&file_info).is_ok());
+        let error = Validator::new(1).with_follow_symlinks(false).validate_file(&file_info).unwrap_err();
+        assert!(error.to_string().contains("is a symlink"), "{}", error);
+        let target_info = utils::get_file_info(target.to_str().unwrap());
+        assert!(Validator::new(1).with_follow_symlinks(false).validate_file(&target_info).is_ok());
+    }
+
+    #[test]
+    fn test_validator_allowed_roots() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let inside = dir.path().join("inside");
+        std::fs::create_dir(&inside).unwrap();
+        let file = dir.path().join("outside.py");
+        std::fs::write(&file, "print(1)\n").unwrap();
+        let file_info = utils::get_file_info(file.to_str().unwrap());
+
+        let error = Validator::new(1).with_allowed_roots(vec![inside]).validate_file(&file_info).unwrap_err();
+        assert!(error.to_string().contains("outside the allowed roots"), "{}", error);
+        assert!(Validator::new(1).with_allowed_roots(vec![dir.path().to_path_buf()]).validate_file(&file_info).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validator_rejects_special_files() {
+        let error = Validator::new(1).validate_file(&utils::get_file_info("/dev/null")).unwrap_err();
+        assert!(error.to_string().contains("is a device file"), "{}", error);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let fifo = dir.path().join("input.py");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let error = Validator::new(1).validate_file(&utils::get_file_info(fifo.to_str().unwrap())).unwrap_err();
+        assert!(error.to_string().contains("is a FIFO"), "{}", error);
+    }
+
     #[test]
     fn test_config_default() {
         let config = Config::default();
@@ -1,62 +1,668 @@
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use code_runner::bench::{self, BenchOptions};
+use code_runner::executor::{EventSink, RunRecord, RunStatus};
+use code_runner::config_file::ConfigFile;
+use code_runner::history::{self, Entry};
+use code_runner::layers::{self, LoadedConfig, Override};
+use code_runner::pipeline::Prepared;
+use code_runner::plan::Plan;
+use code_runner::trust::{self, Allowlist};
+use code_runner::report::{self, OutputFormat};
+use code_runner::watch::{self, WatchOptions};
 use code_runner::{
-    commands, utils, Config, Executor, Result, Validator,
+    batch, cache, doctor, install, pipeline, toolchain, utils, CodeRunnerError, CommandSpec, Config, Executor, Result,
 };
+use std::collections::BTreeSet;
 use std::env;
-use std::process::exit;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+/// Run source files in any of the languages shown by `code-runner list`.
+#[derive(Parser)]
+#[command(name = "code-runner", version, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Run files (the default when no command is given)
+    Run(RunArgs),
+    /// Time repeated runs of a file
+    Bench(BenchArgs),
+    /// List the supported languages and whether they're installed
+    List {
+        /// Print the list as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check toolchains, config files and directories
+    Doctor,
+    /// Show or change the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+        /// Change the nearest .code-runner.toml instead of the user config
+        #[arg(long, global = true)]
+        project: bool,
+    },
+    /// Show or remove leftover build directories
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Approve files to run when require_trust is on
+    Allow {
+        /// Files, including .code-runner.toml files, to approve as they are now
+        #[arg(required = true, value_name = "FILE")]
+        files: Vec<String>,
+        /// Withdraw the approval instead
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Show recent runs
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+        /// Number of runs to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+/// Settings that override the configuration for one invocation.
+#[derive(Args, Default)]
+struct Options {
+    /// Timeout in seconds, for every language
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+    /// Print nothing but errors
+    #[arg(long)]
+    silent: bool,
+    /// Run files as this language, whatever their extension
+    #[arg(long, value_name = "LANGUAGE")]
+    lang: Option<String>,
+    /// Working directory: file_dir, workspace_root, inherit or a path
+    #[arg(long, value_name = "POLICY")]
+    cwd: Option<String>,
+    /// Keep build artifacts after the run
+    #[arg(long)]
+    no_cleanup: bool,
+    /// Build profile, such as release, debug or asan
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+}
+
+impl Options {
+    fn overrides(&self) -> Result<Vec<Override>> {
+        let mut overrides = Vec::new();
+        if let Some(timeout) = self.timeout {
+            // Config values are TOML integers, which are signed.
+            let timeout = i64::try_from(timeout).map_err(|_| CodeRunnerError::ConfigError(format!(
+                "--timeout {} is too large, expected at most {} seconds",
+                timeout,
+                i64::MAX
+            )))?;
+            overrides.push(Override::new("--timeout", "timeout", timeout));
+        }
+        if self.silent {
+            overrides.push(Override::new("--silent", "silent_mode", true));
+        }
+        if let Some(cwd) = &self.cwd {
+            overrides.push(Override::new("--cwd", "cwd", cwd.as_str()));
+        }
+        if self.no_cleanup {
+            overrides.push(Override::new("--no-cleanup", "cleanup_artifacts", false));
+        }
+        if let Some(profile) = &self.profile {
+            overrides.push(Override::new("--profile", "profile", profile.as_str()));
+        }
+        Ok(overrides)
+    }
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Files or glob patterns to run
+    #[arg(required = true, value_name = "FILE")]
+    files: Vec<String>,
+
+    #[command(flatten)]
+    options: Options,
+
+    /// Run several files in parallel with this many jobs
+    #[arg(short = 'j', long)]
+    jobs: Option<usize>,
+    /// Output format: text, json, ndjson, junit or tap
+    #[arg(long, default_value = "text")]
+    format: OutputFormat,
+    /// Re-run on every save
+    #[arg(long, conflicts_with = "jobs")]
+    watch: bool,
+    /// Re-run when any source file in the directory changes
+    #[arg(long, conflicts_with = "jobs")]
+    watch_dir: bool,
+    /// Print what would be executed without running anything
+    #[arg(long)]
+    dry_run: bool,
+    /// Like --dry-run, also showing which setting decided each part
+    #[arg(long)]
+    explain: bool,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    /// File to benchmark
+    #[arg(required = true, value_name = "FILE")]
+    file: Vec<String>,
+
+    #[command(flatten)]
+    options: Options,
+
+    /// Number of timed runs
+    #[arg(short = 'n', long)]
+    runs: Option<usize>,
+    /// Untimed runs before measuring
+    #[arg(long)]
+    warmup: Option<usize>,
+    /// Write the samples and statistics as JSON
+    #[arg(long, value_name = "PATH")]
+    export_json: Option<String>,
+    /// Write the samples as CSV
+    #[arg(long, value_name = "PATH")]
+    export_csv: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration
+    Show {
+        /// Note where each setting comes from
+        #[arg(long)]
+        origin: bool,
+        /// Resolve the project config for this file
+        file: Option<String>,
+    },
+    /// Print the path of the config file
+    Path,
+    /// Write a commented config file
+    Init {
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print one setting
+    Get { key: String },
+    /// Change one setting
+    Set { key: String, value: String },
+    /// Remove one setting
+    Unset { key: String },
+    /// Open the config file in $VISUAL or $EDITOR
+    Edit,
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// List leftover build directories
+    List,
+    /// Print where build directories are created
+    Path,
+    /// Remove build directories no running process owns
+    Clean,
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Forget every recorded run
+    Clear,
+}
 
 fn main() {
     env_logger::init();
-    
-    let args: Vec<String> = env::args().collect();
-    
-    if args.len() < 2 {
-        print_usage(&args);
-        exit(1);
-    }
-    
-    let file = args[1..].join(" ");
-    
-    match run(&file) {
+
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Some(Commands::Run(args)) => run_files(args),
+        Some(Commands::Bench(args)) => run_bench(&args),
+        Some(Commands::List { json }) => list_languages(json),
+        Some(Commands::Doctor) => doctor(),
+        Some(Commands::Config { command, project }) => run_config(command, project),
+        Some(Commands::Cache { command }) => run_cache(command),
+        Some(Commands::Allow { files, revoke }) => run_allow(&files, revoke),
+        Some(Commands::History { command, limit }) => run_history(command, limit),
+        None => run_files(cli.run),
+    };
+
+    match result {
         Ok(_) => exit(0),
         Err(e) => {
             eprintln!("Error: {}", e);
+            if let CodeRunnerError::ProgramNotInstalled(program) = &e {
+                print_install_hints(program);
+            }
             exit(1);
         }
     }
 }
 
-fn run(file: &str) -> Result<()> {
-    let config = Config::load();
-    
-    let validator = Validator::new(config.max_file_size_mb);
-    validator.validate(file)?;
-    
-    let file_info = utils::get_file_info(file);
-    validator.validate_file(&file_info)?;
-    
-    let cmd_spec = commands::get_command(&file_info.ext, &file_info.abs_path)
-        .ok_or_else(|| code_runner::CodeRunnerError::UnsupportedFileType(file_info.ext.clone()))?;
-    
-    if config.check_installed {
-        utils::check_program_installed(&cmd_spec.program)?;
-    }
-    
-    let executor = Executor::new(config.clone());
-    executor.execute(&cmd_spec)?;
-    executor.cleanup(&file_info.ext)?;
-    
+fn print_install_hints(program: &str) {
+    let hints = install::hints(program);
+    if !hints.is_empty() {
+        eprintln!("\nInstall it with:");
+        for hint in hints {
+            eprintln!("  {}", hint);
+        }
+    }
+}
+
+fn run_files(args: RunArgs) -> Result<()> {
+    let file = args.files.join(" ");
+    let RunArgs { files, options, jobs, format, watch, watch_dir, dry_run, explain } = args;
+
+    // Unquoted paths with spaces arrive split into several arguments, so only
+    // treat them as separate files when they don't name one file together.
+    let batch = jobs.is_some()
+        || (!Path::new(&file).exists() && (files.len() > 1 || batch::is_glob(&file)));
+    if batch && (watch || watch_dir) {
+        Cli::command()
+            .error(ErrorKind::ArgumentConflict, "--watch and --watch-dir take a single file")
+            .exit();
+    }
+
+    if dry_run || explain {
+        let files = if batch { batch::expand_patterns(&files) } else { vec![file] };
+        print_plans(&files, &options, explain, format)
+    } else if batch {
+        run_batch(&files, &options, jobs, format)
+    } else if watch || watch_dir {
+        let path = utils::get_file_info(&file).abs_path;
+        let watch_options = WatchOptions { watch_dir, ..WatchOptions::default() };
+        watch::watch(&path, &watch_options, move |cancel| run(&file, &options, Some(cancel), format))
+    } else {
+        run(&file, &options, None, format)
+    }
+}
+
+/// The configuration for `file`, or for the current directory when running
+/// several files.
+fn load_config(file: Option<&str>, options: &Options) -> Result<Config> {
+    load_layers(file, options).map(|loaded| loaded.config)
+}
+
+/// Like [`load_config`], keeping where each setting came from.
+fn load_layers(file: Option<&str>, options: &Options) -> Result<LoadedConfig> {
+    let loaded = load_layers_quietly(file, options)?;
+    print_warnings(&loaded.warnings);
+    Ok(loaded)
+}
+
+/// Like [`load_layers`], leaving the warnings to the caller.
+fn load_layers_quietly(file: Option<&str>, options: &Options) -> Result<LoadedConfig> {
+    let path = file.map(|file| utils::get_file_info(file).abs_path);
+    let mut loaded = layers::load(path.as_deref(), &options.overrides()?)?;
+
+    // `--timeout` applies to every language and wins over the file's directive.
+    loaded.config.timeout_override = options.timeout;
+    loaded.config.lang = options.lang.clone();
+    Ok(loaded)
+}
+
+/// Shows the warnings about a prepared file, unless told to be silent.
+fn prepared(prepared: Prepared, config: &Config) -> CommandSpec {
+    if !config.silent_mode {
+        print_warnings(&prepared.warnings);
+    }
+    prepared.cmd_spec
+}
+
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// Adds a run to the history, which never fails the run itself.
+fn remember(config: &Config, file: &str, language: Option<&str>, record: &RunRecord) {
+    if !config.history {
+        return;
+    }
+    let Some(path) = history::path() else {
+        return;
+    };
+    let file = utils::get_file_info(file).abs_path.display().to_string();
+    if let Err(e) = history::record(&path, &Entry::new(&file, language, record)) {
+        log::warn!("Failed to record history: {}", e);
+    }
+}
+
+fn list_languages(json: bool) -> Result<()> {
+    let config = load_config(None, &Options::default())?;
+    let languages = toolchain::languages(&config);
+    if json {
+        println!("{}", report::encode_pretty(&languages)?);
+    } else {
+        print!("{}", toolchain::render(&languages));
+    }
+    Ok(())
+}
+
+fn doctor() -> Result<()> {
+    // A broken config is one of the things to report, not a reason to stop.
+    let config = layers::load(None, &[]).map(|loaded| loaded.config).unwrap_or_default();
+    let report = doctor::run(&config);
+    print!("{}", report.render());
+
+    match report.problems() {
+        0 => Ok(()),
+        problems => Err(CodeRunnerError::DoctorFindings(problems)),
+    }
+}
+
+/// `code-runner config ...`. Commands that change a file act on the user
+/// config, or on the nearest `.code-runner.toml` with `--project`.
+fn run_config(command: ConfigCommand, project: bool) -> Result<()> {
+    let path = if project { project_config_path()? } else { user_config_path()? };
+
+    match command {
+        ConfigCommand::Show { origin, file } => {
+            let file = file.map(|file| utils::get_file_info(&file).abs_path);
+            let loaded = layers::load(file.as_deref(), &[])?;
+            print_warnings(&loaded.warnings);
+            print!("{}", loaded.render(origin));
+        }
+        ConfigCommand::Path => println!("{}", path.display()),
+        ConfigCommand::Init { force } => {
+            ConfigFile::init(&path, force)?;
+            println!("Created {}", path.display());
+        }
+        ConfigCommand::Get { key } => {
+            let loaded = layers::load(None, &[])?;
+            match loaded.get(&key) {
+                Some(toml::Value::String(value)) => println!("{}", value),
+                Some(value) => println!("{}", value),
+                None => return Err(CodeRunnerError::ConfigError(format!("'{}' is not set", key))),
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let mut file = ConfigFile::open(&path)?;
+            file.set(&key, &value)?;
+            file.save()?;
+        }
+        ConfigCommand::Unset { key } => {
+            let mut file = ConfigFile::open(&path)?;
+            if !file.unset(&key)? {
+                return Err(CodeRunnerError::ConfigError(
+                    format!("'{}' is not set in {}", key, path.display())
+                ));
+            }
+            file.save()?;
+        }
+        ConfigCommand::Edit => edit_config(&path)?,
+    }
+
+    Ok(())
+}
+
+fn user_config_path() -> Result<PathBuf> {
+    Config::config_path().ok_or_else(|| {
+        CodeRunnerError::ConfigError("Cannot determine the user config directory".to_string())
+    })
+}
+
+/// The nearest `.code-runner.toml`, or a new one in the current directory.
+fn project_config_path() -> Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    Ok(layers::find_project_config(&cwd).unwrap_or_else(|| cwd.join(layers::PROJECT_FILE)))
+}
+
+/// Opens the file in `$VISUAL` or `$EDITOR`, then checks what was saved.
+fn edit_config(path: &Path) -> Result<()> {
+    if !path.exists() {
+        ConfigFile::init(path, false)?;
+    }
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad".to_string() } else { "vi".to_string() });
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let status = Command::new(program).args(words).arg(path).status()
+        .map_err(|e| CodeRunnerError::ExecutionFailed(format!("Failed to start '{}': {}", program, e)))?;
+    if !status.success() {
+        return Err(CodeRunnerError::ExecutionFailed(format!("{} exited with {}", program, status)));
+    }
+
+    let (_, warnings) = layers::read_file(path)?;
+    print_warnings(&warnings);
+    Ok(())
+}
+
+fn run_cache(command: CacheCommand) -> Result<()> {
+    match command {
+        CacheCommand::Path => println!("{}", cache::root().display()),
+        CacheCommand::List => {
+            for dir in cache::build_dirs()? {
+                let note = if dir.in_use { "  (in use)" } else { "" };
+                println!("{:>9}  {}{}", cache::format_size(dir.bytes), dir.path.display(), note);
+            }
+        }
+        CacheCommand::Clean => {
+            let removed = cache::clean()?;
+            let bytes = removed.iter().map(|dir| dir.bytes).sum();
+            println!("Removed {} build directories ({})", removed.len(), cache::format_size(bytes));
+        }
+    }
     Ok(())
 }
 
-fn print_usage(args: &[String]) {
-    let program = args.get(0).map(|s| s.as_str()).unwrap_or("code-runner");
-    eprintln!("Usage: {} <file>", program);
-    eprintln!("\nSupported Languages: 35+");
-    eprintln!("  JavaScript, TypeScript, Python, Go, Rust");
-    eprintln!("  C, C++, Java, Kotlin, Scala");
-    eprintln!("  Ruby, PHP, Lua, Perl");
-    eprintln!("  And many more...");
-    eprintln!("\nExamples:");
-    eprintln!("  {} script.py", program);
-    eprintln!("  {} \"file with spaces.js\"", program);
-}
\ No newline at end of file
+fn run_allow(files: &[String], revoke: bool) -> Result<()> {
+    let path = trust::path().ok_or_else(|| {
+        CodeRunnerError::ConfigError("Cannot determine the user data directory".to_string())
+    })?;
+    let mut allowlist = Allowlist::load(&path)?;
+
+    for file in files {
+        let file = utils::get_file_info(file).abs_path;
+        if revoke {
+            if !allowlist.revoke(&file) {
+                return Err(CodeRunnerError::ConfigError(format!("{} is not allowed", file.display())));
+            }
+            println!("Revoked {}", file.display());
+        } else {
+            if !file.is_file() {
+                return Err(CodeRunnerError::FileNotFound(file.display().to_string()));
+            }
+            allowlist.allow(&file)?;
+            println!("Allowed {}", file.display());
+        }
+    }
+    allowlist.save(&path)
+}
+
+fn run_history(command: Option<HistoryCommand>, limit: usize) -> Result<()> {
+    let path = history::path().ok_or_else(|| {
+        CodeRunnerError::ConfigError("Cannot determine the user data directory".to_string())
+    })?;
+
+    match command {
+        Some(HistoryCommand::Clear) => {
+            history::clear(&path)?;
+        }
+        None => {
+            let entries = history::load(&path)?;
+            let skip = entries.len().saturating_sub(limit);
+            print!("{}", history::render(&entries[skip..]));
+        }
+    }
+    Ok(())
+}
+
+fn run(file: &str, options: &Options, cancel: Option<Arc<AtomicBool>>, format: OutputFormat) -> Result<()> {
+    let config = load_config(Some(file), options)?;
+    if format != OutputFormat::Text {
+        return run_structured(file, &config, cancel, format);
+    }
+
+    let cmd_spec = prepared(pipeline::prepare(file, &config)?, &config);
+
+    let mut executor = Executor::new(config.clone());
+    if let Some(cancel) = cancel {
+        executor = executor.with_cancel(cancel);
+    }
+    let record = executor.record(&cmd_spec);
+    executor.cleanup(&cmd_spec)?;
+    remember(&config, file, Some(&cmd_spec.language), &record);
+
+    match record.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Prints what running each file would do, without running anything. Files
+/// that are untrusted or whose programs are missing still get a plan, then
+/// fail like a run would.
+fn print_plans(files: &[String], options: &Options, explain: bool, format: OutputFormat) -> Result<()> {
+    let mut plans = Vec::new();
+    let mut refused = None;
+    for file in files {
+        let loaded = load_layers(Some(file), options)?;
+        let Prepared { file_info, cmd_spec, warnings } = pipeline::resolve(file, &loaded.config)?;
+        if !loaded.config.silent_mode {
+            print_warnings(&warnings);
+        }
+        let mut plan = Plan::new(&file_info, &cmd_spec, &loaded.config)?;
+        if explain {
+            plan.explain(&loaded)?;
+        }
+        plans.push(plan);
+        let runnable = trust::check(&file_info.abs_path, &loaded.config)
+            .and_then(|_| pipeline::check_installed(&cmd_spec, &loaded.config));
+        if let Err(e) = runnable {
+            refused.get_or_insert(e);
+        }
+    }
+
+    match format {
+        OutputFormat::Text => {
+            let rendered: Vec<String> = plans.iter().map(Plan::render).collect();
+            print!("{}", rendered.join("\n"));
+        }
+        _ if plans.len() == 1 => println!("{}", report::encode_pretty(&plans[0])?),
+        _ => println!("{}", report::encode_pretty(&plans)?),
+    }
+
+    match refused {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Runs `file` with its output captured and reported as JSON on stdout.
+fn run_structured(
+    file: &str,
+    config: &Config,
+    cancel: Option<Arc<AtomicBool>>,
+    format: OutputFormat,
+) -> Result<()> {
+    let events: Option<EventSink> = (format == OutputFormat::Ndjson).then(|| {
+        let file = file.to_string();
+        Arc::new(move |event| match report::event_line(&file, &event) {
+            Ok(line) => println!("{}", line),
+            Err(e) => log::warn!("{}", e),
+        }) as EventSink
+    });
+
+    let file_run = batch::run_file(file, config, cancel, events);
+    remember(config, file, file_run.cmd_spec.as_ref().map(|spec| spec.language.as_str()), &file_run.record);
+    match format {
+        OutputFormat::Ndjson => println!("{}", report::run_finished_line(&file_run)?),
+        OutputFormat::Junit => print!("{}", report::junit(std::slice::from_ref(&file_run))),
+        OutputFormat::Tap => print!("{}", report::tap(std::slice::from_ref(&file_run))),
+        _ => println!("{}", report::run_json(&file_run)?),
+    }
+
+    match file_run.record.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn run_batch(patterns: &[String], options: &Options, jobs: Option<usize>, format: OutputFormat) -> Result<()> {
+    // The config here only decides how results are reported; each file runs
+    // with its own.
+    let loaded = load_layers(None, options)?;
+    let config = loaded.config;
+    let jobs = jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+
+    // Files from one project share its warnings, which are printed once.
+    let printed = Mutex::new(loaded.warnings.into_iter().collect::<BTreeSet<_>>());
+    let load = |file: &str| {
+        let loaded = load_layers_quietly(Some(file), options)?;
+        let mut printed = printed.lock().unwrap_or_else(|e| e.into_inner());
+        let fresh: Vec<_> = loaded.warnings.into_iter().filter(|warning| printed.insert(warning.clone())).collect();
+        print_warnings(&fresh);
+        Ok(loaded.config)
+    };
+
+    let files = batch::expand_patterns(patterns);
+    let runs = batch::run(&files, load, jobs, |file_run| match format {
+        OutputFormat::Text if !config.silent_mode => println!("{}", batch::render_file(file_run)),
+        OutputFormat::Ndjson => match report::run_finished_line(file_run) {
+            Ok(line) => println!("{}", line),
+            Err(e) => log::warn!("{}", e),
+        },
+        _ => {}
+    });
+    for file_run in &runs {
+        remember(&config, &file_run.file, file_run.cmd_spec.as_ref().map(|spec| spec.language.as_str()), &file_run.record);
+    }
+    match format {
+        OutputFormat::Text => print!("{}", batch::render_summary(&runs)),
+        OutputFormat::Json => println!("{}", report::batch_json(&runs)?),
+        OutputFormat::Ndjson => println!("{}", report::summary_line(&runs)?),
+        OutputFormat::Junit => print!("{}", report::junit(&runs)),
+        OutputFormat::Tap => print!("{}", report::tap(&runs)),
+    }
+
+    let failed = runs.iter().filter(|run| run.status() != RunStatus::Passed).count();
+    if failed > 0 {
+        return Err(CodeRunnerError::ExecutionFailed(
+            format!("{} of {} files failed", failed, runs.len())
+        ));
+    }
+    Ok(())
+}
+
+fn run_bench(args: &BenchArgs) -> Result<()> {
+    let file = args.file.join(" ");
+    let config = load_config(Some(&file), &args.options)?;
+    let cmd_spec = prepared(pipeline::prepare(&file, &config)?, &config);
+
+    let mut options = BenchOptions::default();
+    options.runs = args.runs.unwrap_or(options.runs);
+    options.warmup = args.warmup.unwrap_or(options.warmup);
+
+    let report = bench::run(&config, &cmd_spec, &options);
+    Executor::new(config).cleanup(&cmd_spec)?;
+    let report = report?;
+
+    print!("{}", report.render());
+    if let Some(path) = &args.export_json {
+        fs::write(path, report.to_json()?)?;
+    }
+    if let Some(path) = &args.export_csv {
+        fs::write(path, report.to_csv())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// Receives output as it is read, for streaming it while the program runs.
+pub type ChunkCallback = Box<dyn FnMut(&str) + Send>;
+
+/// Captures a stream while keeping at most `limit` bytes in memory.
+///
+/// Once the limit is exceeded the first half of the output and the most
+/// recent half are retained, and everything in between is dropped.
+/// A limit of `0` disables truncation.
+pub struct OutputBuffer {
+    limit: usize,
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    total: u64,
+}
+
+impl OutputBuffer {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            total: 0,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.total += data.len() as u64;
+
+        if self.limit == 0 {
+            self.head.extend_from_slice(data);
+            return;
+        }
+
+        let head_limit = self.limit / 2;
+        let tail_limit = self.limit - head_limit;
+
+        let head_room = head_limit.saturating_sub(self.head.len());
+        let (to_head, rest) = data.split_at(head_room.min(data.len()));
+        self.head.extend_from_slice(to_head);
+
+        let rest = if rest.len() > tail_limit {
+            &rest[rest.len() - tail_limit..]
+        } else {
+            rest
+        };
+        self.tail.extend(rest);
+        if self.tail.len() > tail_limit {
+            let excess = self.tail.len() - tail_limit;
+            self.tail.drain(..excess);
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.limit != 0 && self.total > self.limit as u64
+    }
+
+    pub fn into_string(self) -> String {
+        let omitted = self.total - (self.head.len() + self.tail.len()) as u64;
+        let mut bytes = self.head;
+
+        if omitted > 0 {
+            bytes.extend_from_slice(truncation_marker(omitted).as_bytes());
+        }
+        bytes.extend(self.tail);
+
+        String::from_utf8_lossy(&bytes).to_string()
+    }
+}
+
+pub fn truncation_marker(omitted: u64) -> String {
+    format!("\n... [output truncated: {} bytes omitted] ...\n", omitted)
+}
+
+/// Reads `reader` to the end on a background thread.
+///
+/// `exceeded` is raised as soon as the stream produces more than `limit`
+/// bytes, so the caller can stop the program without waiting for it to exit.
+/// `on_chunk` sees the output up to that point, split on UTF-8 boundaries.
+pub fn capture<R>(
+    mut reader: R,
+    limit: usize,
+    exceeded: Arc<AtomicBool>,
+    mut on_chunk: Option<ChunkCallback>,
+) -> JoinHandle<OutputBuffer>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buffer = OutputBuffer::new(limit);
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        let mut pending = Vec::new();
+
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    buffer.push(&chunk[..n]);
+                    if buffer.is_truncated() {
+                        exceeded.store(true, Ordering::SeqCst);
+                    } else if let Some(callback) = on_chunk.as_mut() {
+                        pending.extend_from_slice(&chunk[..n]);
+                        emit_complete_utf8(&mut pending, callback);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+
+        if let Some(callback) = on_chunk.as_mut() {
+            if !pending.is_empty() {
+                callback(&String::from_utf8_lossy(&pending));
+            }
+        }
+
+        buffer
+    })
+}
+
+/// Passes on everything in `pending` except a trailing, incomplete UTF-8
+/// sequence, which is kept until the rest of it arrives.
+fn emit_complete_utf8(pending: &mut Vec<u8>, callback: &mut ChunkCallback) {
+    let complete = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+
+    if complete > 0 {
+        callback(&String::from_utf8_lossy(&pending[..complete]));
+        pending.drain(..complete);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_limit_is_untouched() {
+        let mut buffer = OutputBuffer::new(16);
+        buffer.push(b"hello ");
+        buffer.push(b"world");
+        assert!(!buffer.is_truncated());
+        assert_eq!(buffer.into_string(), "hello world");
+    }
+
+    #[test]
+    fn test_keeps_head_and_tail() {
+        let mut buffer = OutputBuffer::new(8);
+        for chunk in [b"abcd".as_slice(), b"efgh", b"ijkl", b"mnop"] {
+            buffer.push(chunk);
+        }
+        assert!(buffer.is_truncated());
+        assert_eq!(buffer.total_bytes(), 16);
+        assert_eq!(buffer.into_string(), format!("abcd{}mnop", truncation_marker(8)));
+    }
+
+    #[test]
+    fn test_large_single_chunk() {
+        let mut buffer = OutputBuffer::new(4);
+        buffer.push(b"0123456789");
+        assert_eq!(buffer.into_string(), format!("01{}89", truncation_marker(6)));
+    }
+
+    #[test]
+    fn test_zero_limit_is_unbounded() {
+        let mut buffer = OutputBuffer::new(0);
+        buffer.push(&[b'x'; 100_000]);
+        assert!(!buffer.is_truncated());
+        assert_eq!(buffer.into_string().len(), 100_000);
+    }
+
+    #[test]
+    fn test_capture_flags_exceeded() {
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let data = vec![b'y'; 1000];
+        let buffer = capture(std::io::Cursor::new(data), 10, exceeded.clone(), None)
+            .join()
+            .unwrap();
+        assert!(exceeded.load(Ordering::SeqCst));
+        assert!(buffer.is_truncated());
+    }
+
+    #[test]
+    fn test_chunks_split_on_char_boundaries() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let mut callback: ChunkCallback = Box::new(move |text| sink.lock().unwrap().push(text.to_string()));
+
+        let mut pending = "h\u{e9}".as_bytes()[..2].to_vec();
+        emit_complete_utf8(&mut pending, &mut callback);
+        pending.extend_from_slice(&"h\u{e9}".as_bytes()[2..]);
+        emit_complete_utf8(&mut pending, &mut callback);
+
+        assert_eq!(*seen.lock().unwrap(), ["h", "\u{e9}"]);
+    }
+}
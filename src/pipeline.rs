@@ -0,0 +1,73 @@
+use crate::directives::Directives;
+use crate::{commands, trust, utils, CodeRunnerError, CommandSpec, Config, FileInfo, Result, Validator};
+
+/// A file that passed validation, with the command that runs it.
+pub struct Prepared {
+    pub file_info: FileInfo,
+    pub cmd_spec: CommandSpec,
+    /// Problems with the file that don't stop it from running, left to the
+    /// caller to show.
+    pub warnings: Vec<String>,
+}
+
+/// Validates `file` and resolves the command that runs it under `config`.
+pub fn prepare(file: &str, config: &Config) -> Result<Prepared> {
+    let prepared = resolve_with(file, config, true)?;
+    check_installed(&prepared.cmd_spec, config)?;
+    Ok(prepared)
+}
+
+/// Like [`prepare`], for showing what a run would do: the file isn't checked
+/// for trust or installed programs, and no build directory is created, only
+/// named by a placeholder.
+pub fn resolve(file: &str, config: &Config) -> Result<Prepared> {
+    resolve_with(file, config, false)
+}
+
+/// Before a run, an untrusted file is refused right after validation, before
+/// anything in it, like its directives, is acted on.
+fn resolve_with(file: &str, config: &Config, for_run: bool) -> Result<Prepared> {
+    config.check_profile()?;
+
+    let validator = Validator::new(config.max_file_size_mb)
+        .with_follow_symlinks(config.follow_symlinks)
+        .with_allowed_roots(config.allowed_roots.clone());
+    validator.validate(file)?;
+
+    let mut file_info = utils::get_file_info(file);
+    if let Some(name) = &config.lang {
+        let language = commands::language_named(name)
+            .or_else(|| commands::find_language(name))
+            .ok_or_else(|| CodeRunnerError::ConfigError(format!("Unknown language '{}'", name)))?;
+        file_info.ext = language.extensions[0].to_string();
+    }
+    let warnings = validator.validate_file(&file_info)?;
+    if for_run {
+        trust::check(&file_info.abs_path, config)?;
+    }
+
+    let language = commands::find_language(&file_info.ext)
+        .ok_or_else(|| CodeRunnerError::UnsupportedFileType(file_info.ext.clone()))?;
+    let directives = Directives::read(&file_info.abs_path, language)?;
+    let cmd_spec = if for_run {
+        commands::get_command(&file_info.ext, &file_info.abs_path, config, &directives)?
+    } else {
+        commands::preview_command(&file_info.ext, &file_info.abs_path, config, &directives)?
+    };
+    let cwd = config.cwd_for(&cmd_spec.language).resolve(&file_info.abs_path)?;
+    Ok(Prepared { file_info, cmd_spec: cmd_spec.with_cwd(cwd), warnings })
+}
+
+/// Checks that every program `cmd_spec` needs is installed, unless the
+/// configuration turns the check off for its language.
+pub fn check_installed(cmd_spec: &CommandSpec, config: &Config) -> Result<()> {
+    let Some(language) = commands::language_named(&cmd_spec.language) else {
+        return Ok(());
+    };
+    if config.check_installed_for(language.name) {
+        for program in commands::required_programs(language, config)? {
+            utils::check_program_installed(&program)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,350 @@
+//! What a run would do, for `--dry-run`, and why, for `--explain`.
+
+use crate::commands;
+use crate::directives::Directives;
+use crate::layers::{LoadedConfig, Origin};
+use crate::{env, utils, CommandSpec, Config, FileInfo, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Width of the label column in [`Plan::render`].
+const LABEL_WIDTH: usize = 10;
+
+/// Everything running one file would do.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub file: PathBuf,
+    pub language: String,
+    pub stages: Vec<PlannedStage>,
+    /// Directory the stages start in; `None` inherits code-runner's.
+    pub cwd: Option<PathBuf>,
+    /// Variables set on top of the inherited environment.
+    pub env: BTreeMap<String, String>,
+    /// Whether the inherited environment is cleared first.
+    pub clear_env: bool,
+    pub stdin: Option<PathBuf>,
+    /// Timeout in seconds for each stage.
+    pub timeout: u64,
+    pub max_output_bytes: u64,
+    pub kill_on_output_limit: bool,
+    /// Build directory the compile stage writes to. Each run creates a fresh
+    /// one, so this is only a placeholder for its name.
+    pub build_dir: Option<PathBuf>,
+    /// Whether the build directory is removed afterwards.
+    pub cleanup: bool,
+    /// Why each part of the plan is what it is, filled in by [`Plan::explain`].
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub why: BTreeMap<&'static str, Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedStage {
+    pub argv: Vec<String>,
+    /// Where the program was found on `PATH`.
+    pub path: Option<PathBuf>,
+    /// Whether the program is built by an earlier stage.
+    pub artifact: bool,
+}
+
+impl Plan {
+    /// The plan for running `cmd_spec`, as resolved for `file_info`.
+    pub fn new(file_info: &FileInfo, cmd_spec: &CommandSpec, config: &Config) -> Result<Self> {
+        let environment = config.environment(&cmd_spec.language)?;
+        let stages = cmd_spec
+            .stages()
+            .into_iter()
+            .map(|stage| {
+                let artifact = cmd_spec.build_dir.as_ref().is_some_and(|dir| Path::new(&stage.program).starts_with(dir));
+                let path = if artifact { None } else { utils::find_program(&stage.program) };
+                let argv = std::iter::once(stage.program).chain(stage.args).collect();
+                PlannedStage { argv, path, artifact }
+            })
+            .collect();
+
+        Ok(Self {
+            file: file_info.abs_path.clone(),
+            language: cmd_spec.language.clone(),
+            stages,
+            cwd: cmd_spec.cwd.clone(),
+            env: environment.vars,
+            clear_env: environment.clear,
+            stdin: cmd_spec.stdin.clone(),
+            timeout: config.stage_timeout(&cmd_spec.language, cmd_spec.timeout),
+            max_output_bytes: config.max_output_bytes,
+            kill_on_output_limit: config.kill_on_output_limit,
+            build_dir: cmd_spec.build_dir.clone(),
+            cleanup: config.cleanup_artifacts,
+            why: BTreeMap::new(),
+        })
+    }
+
+    /// Notes which detection rule, config layer or directive decided each
+    /// part of the plan.
+    pub fn explain(&mut self, loaded: &LoadedConfig) -> Result<()> {
+        let config = &loaded.config;
+        let origins = &loaded.origins;
+        let set_by = |key: &str| set_by(key, origins);
+        let Some(language) = commands::language_named(&self.language) else {
+            return Ok(());
+        };
+        let lang = config.language(language.name);
+        let directives = Directives::read(&self.file, language)?;
+        let language_key = |name: &str| format!("languages.{}.{}", language.name, name);
+        let mut why: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+
+        let ext = self.file.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let detected = match &config.lang {
+            Some(_) => "set by command line --lang".to_string(),
+            None => format!("the .{} extension belongs to {}", ext, language.name),
+        };
+        why.entry("language").or_default().push(detected);
+        if let Some(shebang) = shebang(&self.file) {
+            why.entry("language").or_default()
+                .push(format!("the shebang `{}` is ignored; languages are detected by extension", shebang));
+        }
+
+        let stages = why.entry("stages").or_default();
+        stages.push(format!("built-in command for {}", language.name));
+        let profile_flags = config.profile_flags(language.name);
+        if !profile_flags.is_empty() {
+            stages.push(format!("profile flags {}: {}", profile_flags.join(" "), set_by("profile")));
+        }
+        if let Some(compiler) = lang.and_then(|lang| lang.compiler.as_ref()) {
+            stages.push(format!("compiler {}: {}", compiler, set_by(&language_key("compiler"))));
+        }
+        if let Some(flags) = lang.map(|lang| &lang.compiler_flags).filter(|flags| !flags.is_empty()) {
+            stages.push(format!("compiler flags {}: {}", flags.join(" "), set_by(&language_key("compiler_flags"))));
+        }
+        if let Some(runner) = lang.and_then(|lang| lang.runner.as_ref()) {
+            stages.push(format!("runner {}: {}", runner, set_by(&language_key("runner"))));
+        }
+        if !directives.flags.is_empty() {
+            stages.push(format!("flags {}: {}", directives.flags.join(" "), DIRECTIVE));
+        }
+        if !directives.args.is_empty() {
+            stages.push(format!("args {}: {}", directives.args.join(" "), DIRECTIVE));
+        }
+
+        let cwd_key = if lang.is_some_and(|lang| lang.cwd.is_some()) { language_key("cwd") } else { "cwd".to_string() };
+        why.entry("cwd").or_default().push(format!("{}: {}", config.cwd_for(language.name), set_by(&cwd_key)));
+
+        let env = why.entry("env").or_default();
+        for name in self.env.keys() {
+            env.push(format!("{}: {}", name, env_source(name, language.name, config, origins)));
+        }
+        let clear_key = if lang.is_some_and(|lang| lang.clear_env.is_some()) { language_key("clear_env") } else { "clear_env".to_string() };
+        env.push(set_by(&clear_key));
+
+        if self.stdin.is_some() {
+            why.entry("stdin").or_default().push(DIRECTIVE.to_string());
+        }
+
+        let timeout = if config.timeout_override.is_some() {
+            set_by("timeout")
+        } else if directives.timeout.is_some() {
+            DIRECTIVE.to_string()
+        } else if lang.is_some_and(|lang| lang.timeout.is_some()) {
+            set_by(&language_key("timeout"))
+        } else {
+            set_by("timeout")
+        };
+        why.entry("timeout").or_default().push(timeout);
+        why.entry("output").or_default().extend([set_by("max_output_bytes"), set_by("kill_on_output_limit")]);
+        if self.build_dir.is_some() {
+            why.entry("artifacts").or_default().push(set_by("cleanup_artifacts"));
+        }
+
+        self.why = why;
+        Ok(())
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut line = |label: &str, value: &str, key: Option<&str>| {
+            let _ = writeln!(out, "{:<width$} {}", label, value, width = LABEL_WIDTH);
+            for reason in key.and_then(|key| self.why.get(key)).into_iter().flatten() {
+                let _ = writeln!(out, "{:<width$} # {}", "", reason, width = LABEL_WIDTH);
+            }
+        };
+
+        line("File", &self.file.display().to_string(), None);
+        line("Language", &self.language, Some("language"));
+        for (index, stage) in self.stages.iter().enumerate() {
+            let program = match (&stage.path, stage.artifact) {
+                (_, true) => "built by an earlier stage".to_string(),
+                (Some(path), _) => path.display().to_string(),
+                (None, _) => "not found".to_string(),
+            };
+            line(&format!("Stage {}", index + 1), &shell_words(&stage.argv), None);
+            line("", &format!("program: {}", program), None);
+        }
+        if let Some(reasons) = self.why.get("stages") {
+            for reason in reasons {
+                line("", &format!("# {}", reason), None);
+            }
+        }
+
+        let cwd = match &self.cwd {
+            Some(cwd) => cwd.display().to_string(),
+            None => match std::env::current_dir() {
+                Ok(dir) => format!("inherited ({})", dir.display()),
+                Err(_) => "inherited".to_string(),
+            },
+        };
+        line("Cwd", &cwd, Some("cwd"));
+
+        let mut env: Vec<String> = self.env.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        if self.clear_env {
+            env.insert(0, format!("cleared, except {}", env::PRESERVED_VARS.join(", ")));
+        } else if env.is_empty() {
+            env.push("unchanged".to_string());
+        }
+        for (index, setting) in env.iter().enumerate() {
+            let last = index + 1 == env.len();
+            line(if index == 0 { "Env" } else { "" }, setting, last.then_some("env"));
+        }
+
+        let stdin = self.stdin.as_ref().map_or_else(|| "inherited".to_string(), |path| path.display().to_string());
+        line("Stdin", &stdin, Some("stdin"));
+        line("Timeout", &format!("{}s per stage", self.timeout), Some("timeout"));
+        let over = if self.kill_on_output_limit { "then the program is stopped" } else { "then it is truncated" };
+        line("Output", &format!("up to {} bytes per stream, {}", self.max_output_bytes, over), Some("output"));
+
+        let artifacts = match (&self.build_dir, self.cleanup) {
+            (Some(dir), true) => format!("{} (created for the run, removed afterwards)", dir.display()),
+            (Some(dir), false) => format!("{} (created for the run, kept)", dir.display()),
+            (None, _) => "none".to_string(),
+        };
+        line("Artifacts", &artifacts, Some("artifacts"));
+        out
+    }
+}
+
+const DIRECTIVE: &str = "code-runner: directive in the file";
+
+fn set_by(key: &str, origins: &BTreeMap<String, Origin>) -> String {
+    match origins.get(key) {
+        Some(Origin::Default) | None => format!("{} is the default", key),
+        Some(origin) => format!("{} is set by {}", key, origin),
+    }
+}
+
+/// Where the value of the environment variable `name` comes from. Language
+/// settings win over global ones, and `env` entries over `env_file` contents.
+fn env_source(name: &str, language: &str, config: &Config, origins: &BTreeMap<String, Origin>) -> String {
+    let lang = config.language(language);
+    if lang.is_some_and(|lang| lang.env.contains_key(name)) {
+        return set_by(&format!("languages.{}.env.{}", language, name), origins);
+    }
+    if let Some(file) = lang.and_then(|lang| lang.env_file.as_ref()).filter(|file| defines(file, name)) {
+        return format!("set by env file {}", file.display());
+    }
+    if config.env.contains_key(name) {
+        return set_by(&format!("env.{}", name), origins);
+    }
+    match &config.env_file {
+        Some(file) => format!("set by env file {}", file.display()),
+        None => "set by the configuration".to_string(),
+    }
+}
+
+fn defines(env_file: &Path, name: &str) -> bool {
+    fs::read_to_string(env_file)
+        .ok()
+        .and_then(|content| env::parse_dotenv(&content, &env_file.display().to_string()).ok())
+        .is_some_and(|vars| vars.contains_key(name))
+}
+
+fn shebang(path: &Path) -> Option<String> {
+    let content = fs::read(path).ok()?;
+    let first = content.split(|byte| *byte == b'\n').next()?;
+    let first = String::from_utf8_lossy(first);
+    first.starts_with("#!").then(|| first.trim_end().to_string())
+}
+
+/// `argv` as it could be typed into a POSIX shell.
+fn shell_words(argv: &[String]) -> String {
+    argv.iter()
+        .map(|word| {
+            let plain = !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if plain { word.clone() } else { format!("'{}'", word.replace('\'', "'\\''")) }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LanguageConfig;
+    use crate::layers;
+
+    fn plan_for(content: &str, config: &Config) -> (tempfile::TempDir, Plan) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("main.py");
+        fs::write(&file, content).unwrap();
+        let file_info = utils::get_file_info(file.to_str().unwrap());
+        let directives = Directives::read(&file_info.abs_path, commands::language_named("python").unwrap()).unwrap();
+        let cmd_spec = commands::preview_command("py", &file_info.abs_path, config, &directives).unwrap();
+        let plan = Plan::new(&file_info, &cmd_spec, config).unwrap();
+        (dir, plan)
+    }
+
+    #[test]
+    fn test_plan_resolves_the_run() {
+        let mut config = Config::default();
+        config.env.insert("DEBUG".to_string(), "1".to_string());
+        let (_dir, plan) = plan_for("# code-runner: args=\"a b\" timeout=3\nprint(1)\n", &config);
+
+        assert_eq!(plan.language, "python");
+        assert_eq!(plan.stages.len(), 1);
+        assert_eq!(plan.stages[0].argv[2..], ["a", "b"]);
+        assert_eq!(plan.timeout, 3);
+        assert_eq!(plan.env.get("DEBUG").map(String::as_str), Some("1"));
+
+        let text = plan.render();
+        assert!(text.contains("Language   python\n"), "{}", text);
+        assert!(text.contains("Timeout    3s per stage\n"), "{}", text);
+        assert!(text.contains("Env        DEBUG=1\n"), "{}", text);
+        assert!(text.contains("Artifacts  none\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_cleared_env_lists_what_is_kept() {
+        let config = Config { clear_env: true, ..Config::default() };
+        let (_dir, plan) = plan_for("print(1)\n", &config);
+
+        let kept = format!("Env        cleared, except {}\n", env::PRESERVED_VARS.join(", "));
+        assert!(plan.render().contains(&kept), "{}", plan.render());
+    }
+
+    #[test]
+    fn test_explain_names_the_deciding_layer() {
+        let mut config = Config::default();
+        config.languages.insert(
+            "python".to_string(),
+            LanguageConfig { runner: Some("pypy3".to_string()), ..LanguageConfig::default() },
+        );
+        let (_dir, mut plan) = plan_for("#!/usr/bin/env python3\n# code-runner: timeout=3\n", &config);
+
+        let mut loaded = layers::defaults().unwrap();
+        loaded.config = config;
+        loaded.origins.insert("languages.python.runner".to_string(), Origin::Env("CODE_RUNNER_LANGUAGES__PYTHON__RUNNER".to_string()));
+        plan.explain(&loaded).unwrap();
+
+        assert_eq!(plan.why["language"][0], "the .py extension belongs to python");
+        assert!(plan.why["language"][1].contains("`#!/usr/bin/env python3` is ignored"));
+        assert!(plan.why["stages"].contains(&"runner pypy3: languages.python.runner is set by environment CODE_RUNNER_LANGUAGES__PYTHON__RUNNER".to_string()));
+        assert_eq!(plan.why["timeout"], [DIRECTIVE]);
+        assert_eq!(plan.why["output"][0], "max_output_bytes is the default");
+    }
+
+    #[test]
+    fn test_shell_words() {
+        let argv = ["gcc", "-O2", "my file.c", "it's"].map(String::from);
+        assert_eq!(shell_words(&argv), "gcc -O2 'my file.c' 'it'\\''s'");
+    }
+}
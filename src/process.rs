@@ -0,0 +1,323 @@
+//! Process group handling for executed programs.
+//!
+//! On Unix every program runs in its own process group so that the whole tree
+//! (e.g. a shell script and everything it started) can be stopped at once.
+//! When we own the terminal and run one program at a time, the group is also
+//! made the foreground group so the program can read from it and receives
+//! Ctrl-C directly. Otherwise SIGINT, SIGTERM and SIGHUP sent to us are passed
+//! on to every running group before we exit, so that nothing is left behind.
+
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+use std::time::{Duration, Instant};
+
+/// Resources used by a finished process, where the platform reports them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Usage {
+    pub cpu_time: Duration,
+    pub peak_memory_kb: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Exit {
+    pub status: ExitStatus,
+    pub wall_time: Duration,
+    pub usage: Option<Usage>,
+}
+
+/// A spawned child that can be waited on with a timeout and killed as a group.
+pub struct RunningProcess {
+    child: Child,
+    started: Instant,
+    #[cfg(unix)]
+    foreground: bool,
+    #[cfg(unix)]
+    waiter: std::sync::mpsc::Receiver<io::Result<Exit>>,
+}
+
+impl RunningProcess {
+    /// Spawns `command`, handing it the terminal if we own it.
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        Self::start(command, owns_terminal())
+    }
+
+    /// Spawns `command` without ever handing it the terminal, for programs
+    /// that run alongside others.
+    pub fn spawn_in_background(command: &mut Command) -> io::Result<Self> {
+        Self::start(command, false)
+    }
+
+    fn start(command: &mut Command, foreground: bool) -> io::Result<Self> {
+        configure(command, foreground);
+
+        let started = Instant::now();
+        let child = command.spawn()?;
+        #[cfg(unix)]
+        groups::add(child.id() as libc::pid_t);
+
+        #[cfg(unix)]
+        let waiter = {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let pid = child.id() as libc::pid_t;
+            std::thread::spawn(move || {
+                let _ = tx.send(wait4(pid, started));
+            });
+            rx
+        };
+
+        Ok(Self {
+            child,
+            started,
+            #[cfg(unix)]
+            foreground,
+            #[cfg(unix)]
+            waiter,
+        })
+    }
+
+    pub fn child_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    #[cfg(unix)]
+    pub fn wait_timeout(&mut self, timeout: Duration) -> io::Result<Option<Exit>> {
+        use std::sync::mpsc::RecvTimeoutError;
+
+        match self.waiter.recv_timeout(timeout) {
+            Ok(exit) => {
+                groups::remove(self.child.id() as libc::pid_t);
+                self.reclaim_terminal();
+                exit.map(Some)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::other("process waiter stopped")),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn wait_timeout(&mut self, timeout: Duration) -> io::Result<Option<Exit>> {
+        use wait_timeout::ChildExt;
+
+        Ok(self.child.wait_timeout(timeout)?.map(|status| Exit {
+            status,
+            wall_time: self.started.elapsed(),
+            usage: None,
+        }))
+    }
+
+    /// Kills the child together with anything it spawned, so that compiled
+    /// binaries and helper processes don't outlive the runner.
+    #[cfg(unix)]
+    pub fn kill_tree(&mut self) -> io::Result<()> {
+        let pgid = self.child.id() as libc::pid_t;
+        unsafe {
+            libc::kill(-pgid, libc::SIGKILL);
+        }
+        let _ = self.waiter.recv();
+        groups::remove(pgid);
+        self.reclaim_terminal();
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn kill_tree(&mut self) -> io::Result<()> {
+        self.child.kill()?;
+        self.child.wait()?;
+        Ok(())
+    }
+
+    /// Takes the terminal back after a program that was given it has finished.
+    #[cfg(unix)]
+    fn reclaim_terminal(&self) {
+        if !self.foreground {
+            return;
+        }
+        unsafe {
+            let ours = libc::getpgrp();
+            if libc::isatty(libc::STDIN_FILENO) == 1 && libc::tcgetpgrp(libc::STDIN_FILENO) != ours {
+                with_sigttou_blocked(|| libc::tcsetpgrp(libc::STDIN_FILENO, ours));
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RunningProcess {
+    fn drop(&mut self) {
+        groups::remove(self.child.id() as libc::pid_t);
+    }
+}
+
+#[cfg(unix)]
+fn configure(command: &mut Command, foreground: bool) {
+    use std::os::unix::process::CommandExt;
+
+    groups::install_handlers();
+    command.process_group(0);
+
+    if foreground {
+        unsafe {
+            command.pre_exec(|| {
+                libc::setpgid(0, 0);
+                with_sigttou_blocked(|| libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp()));
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn configure(_command: &mut Command, _foreground: bool) {}
+
+/// The process groups of running children, kept where a signal handler can
+/// read them without locking.
+#[cfg(unix)]
+mod groups {
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Once;
+
+    const SLOTS: usize = 64;
+    const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    static GROUPS: [AtomicI32; SLOTS] = [const { AtomicI32::new(0) }; SLOTS];
+
+    /// Starts tracking `pgid`. With every slot taken the group is not
+    /// tracked, and is only stopped by signals that reach it directly.
+    pub fn add(pgid: libc::pid_t) {
+        for slot in &GROUPS {
+            if slot.compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                return;
+            }
+        }
+    }
+
+    pub fn remove(pgid: libc::pid_t) {
+        for slot in &GROUPS {
+            let _ = slot.compare_exchange(pgid, 0, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+
+    /// Installs the forwarding handlers, leaving alone signals that were
+    /// ignored when we started (as under `nohup`).
+    pub fn install_handlers() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| unsafe {
+            for signal in SIGNALS {
+                let mut previous: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(signal, std::ptr::null(), &mut previous) != 0
+                    || previous.sa_sigaction == libc::SIG_IGN
+                {
+                    continue;
+                }
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = forward as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signal, &action, std::ptr::null_mut());
+            }
+        });
+    }
+
+    /// Passes `signal` on to every running group, then dies of it ourselves.
+    /// Only async-signal-safe calls are made here.
+    extern "C" fn forward(signal: libc::c_int) {
+        for slot in &GROUPS {
+            let pgid = slot.load(Ordering::SeqCst);
+            if pgid > 0 {
+                unsafe {
+                    libc::kill(-pgid, signal);
+                }
+            }
+        }
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
+
+/// Reaps `pid` with `wait4`, which also reports CPU time and peak RSS.
+/// `Child::wait` must not be used afterwards, as the process is gone.
+#[cfg(unix)]
+fn wait4(pid: libc::pid_t, started: Instant) -> io::Result<Exit> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    loop {
+        let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) };
+        if ret == pid {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    let wall_time = started.elapsed();
+    let timeval = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+
+    // Linux reports ru_maxrss in kilobytes, macOS in bytes.
+    let peak_memory_kb = if cfg!(target_os = "macos") {
+        rusage.ru_maxrss as u64 / 1024
+    } else {
+        rusage.ru_maxrss as u64
+    };
+
+    Ok(Exit {
+        status: ExitStatus::from_raw(status),
+        wall_time,
+        usage: Some(Usage {
+            cpu_time: timeval(rusage.ru_utime) + timeval(rusage.ru_stime),
+            peak_memory_kb,
+        }),
+    })
+}
+
+#[cfg(unix)]
+fn owns_terminal() -> bool {
+    unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+    }
+}
+
+#[cfg(not(unix))]
+fn owns_terminal() -> bool {
+    false
+}
+
+/// `tcsetpgrp` from a background group raises SIGTTOU, which would stop us.
+/// Blocking it only affects the calling thread, unlike changing its handler.
+#[cfg(unix)]
+unsafe fn with_sigttou_blocked<F: FnOnce() -> libc::c_int>(f: F) {
+    let mut blocked: libc::sigset_t = std::mem::zeroed();
+    let mut previous: libc::sigset_t = std::mem::zeroed();
+    libc::sigemptyset(&mut blocked);
+    libc::sigaddset(&mut blocked, libc::SIGTTOU);
+    libc::pthread_sigmask(libc::SIG_BLOCK, &blocked, &mut previous);
+    f();
+    libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+}
+
+/// Whether a process with this id exists.
+#[cfg(unix)]
+pub fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    unsafe { libc::kill(pid, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+/// Without a cheap way to ask, only our own process counts as running.
+#[cfg(not(unix))]
+pub fn is_alive(pid: u32) -> bool {
+    pid == std::process::id()
+}
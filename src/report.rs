@@ -0,0 +1,341 @@
+use crate::batch::FileRun;
+use crate::executor::{ExecutionEvent, RunStatus};
+use crate::{CodeRunnerError, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// How results are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Program output as-is, with human-readable status lines.
+    #[default]
+    Text,
+    /// One JSON document once everything has finished.
+    Json,
+    /// One JSON event per line, written while programs run.
+    Ndjson,
+    /// A JUnit XML report with one test case per file.
+    Junit,
+    /// A Test Anything Protocol stream with one test point per file.
+    Tap,
+}
+
+impl FromStr for OutputFormat {
+    type Err = CodeRunnerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "junit" => Ok(OutputFormat::Junit),
+            "tap" => Ok(OutputFormat::Tap),
+            other => Err(CodeRunnerError::ConfigError(format!(
+                "Unknown format '{}', expected text, json, ndjson, junit or tap",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+}
+
+impl Summary {
+    pub fn of(runs: &[FileRun]) -> Self {
+        let count = |status: RunStatus| runs.iter().filter(|run| run.status() == status).count();
+        let passed = count(RunStatus::Passed);
+        let timed_out = count(RunStatus::TimedOut);
+
+        Self {
+            total: runs.len(),
+            passed,
+            failed: runs.len() - passed - timed_out,
+            timed_out,
+        }
+    }
+}
+
+pub fn run_json(file_run: &FileRun) -> Result<String> {
+    encode_pretty(file_run)
+}
+
+pub fn batch_json(runs: &[FileRun]) -> Result<String> {
+    #[derive(Serialize)]
+    struct Batch<'a> {
+        runs: &'a [FileRun],
+        summary: Summary,
+    }
+
+    encode_pretty(&Batch { runs, summary: Summary::of(runs) })
+}
+
+/// An NDJSON line for an event from the run of `file`.
+pub fn event_line(file: &str, event: &ExecutionEvent) -> Result<String> {
+    #[derive(Serialize)]
+    struct Line<'a> {
+        file: &'a str,
+        #[serde(flatten)]
+        event: &'a ExecutionEvent,
+    }
+
+    encode(&Line { file, event })
+}
+
+/// The final NDJSON line for one file, carrying the whole record.
+pub fn run_finished_line(file_run: &FileRun) -> Result<String> {
+    #[derive(Serialize)]
+    struct Line<'a> {
+        event: &'static str,
+        #[serde(flatten)]
+        run: &'a FileRun,
+    }
+
+    encode(&Line { event: "run_finished", run: file_run })
+}
+
+pub fn summary_line(runs: &[FileRun]) -> Result<String> {
+    #[derive(Serialize)]
+    struct Line {
+        event: &'static str,
+        #[serde(flatten)]
+        summary: Summary,
+    }
+
+    encode(&Line { event: "summary", summary: Summary::of(runs) })
+}
+
+/// Renders `runs` as a JUnit XML test suite. Files that could not be run are
+/// reported as errors, and files that ran but did not pass as failures.
+pub fn junit(runs: &[FileRun]) -> String {
+    let summary = Summary::of(runs);
+    let errors = runs.iter().filter(|run| run.cmd_spec.is_none()).count();
+    let failures = runs.len() - summary.passed - errors;
+    let time: f64 = runs.iter().map(|run| run.record.wall_time().as_secs_f64()).sum();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+        runs.len(), failures, errors, time
+    );
+    let _ = writeln!(
+        out,
+        "  <testsuite name=\"code-runner\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"0\" time=\"{:.3}\">",
+        runs.len(), failures, errors, time
+    );
+
+    for run in runs {
+        let language = run.cmd_spec.as_ref().map_or("unknown", |spec| spec.language.as_str());
+        let _ = writeln!(
+            out,
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">",
+            xml_escape(&run.file),
+            xml_escape(language),
+            run.record.wall_time().as_secs_f64()
+        );
+
+        if run.status() != RunStatus::Passed {
+            let element = if run.cmd_spec.is_none() { "error" } else { "failure" };
+            let _ = writeln!(
+                out,
+                "      <{} type=\"{}\" message=\"{}\"/>",
+                element,
+                xml_escape(run.status().label()),
+                xml_escape(&failure_message(run))
+            );
+        }
+
+        let (stdout, stderr) = captured_output(run);
+        if !stdout.is_empty() {
+            let _ = writeln!(out, "      <system-out>{}</system-out>", xml_escape(&stdout));
+        }
+        if !stderr.is_empty() {
+            let _ = writeln!(out, "      <system-err>{}</system-err>", xml_escape(&stderr));
+        }
+        out.push_str("    </testcase>\n");
+    }
+
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+/// Renders `runs` as TAP version 13, with a YAML diagnostic block carrying the
+/// timing and captured output of each file.
+pub fn tap(runs: &[FileRun]) -> String {
+    let mut out = String::from("TAP version 13\n");
+    let _ = writeln!(out, "1..{}", runs.len());
+
+    for (i, run) in runs.iter().enumerate() {
+        let verdict = if run.status() == RunStatus::Passed { "ok" } else { "not ok" };
+        let _ = writeln!(out, "{} {} - {}", verdict, i + 1, run.file.replace('#', "\\#"));
+
+        out.push_str("  ---\n");
+        let _ = writeln!(out, "  status: {}", run.status().label());
+        let _ = writeln!(out, "  duration_ms: {:.3}", run.record.wall_time().as_secs_f64() * 1000.0);
+        if run.status() != RunStatus::Passed {
+            let _ = writeln!(out, "  message: {}", yaml_quote(&failure_message(run)));
+        }
+        let (stdout, stderr) = captured_output(run);
+        yaml_block(&mut out, "stdout", &stdout);
+        yaml_block(&mut out, "stderr", &stderr);
+        out.push_str("  ...\n");
+    }
+
+    out
+}
+
+fn failure_message(run: &FileRun) -> String {
+    match &run.record.error {
+        Some(error) => error.to_string(),
+        None => run.status().label().to_string(),
+    }
+}
+
+/// Output of every stage that ran, in order.
+fn captured_output(run: &FileRun) -> (String, String) {
+    let stdout = run.record.results.iter().map(|result| result.stdout.as_str()).collect();
+    let stderr = run.record.results.iter().map(|result| result.stderr.as_str()).collect();
+    (stdout, stderr)
+}
+
+/// Escapes markup characters and drops those XML 1.0 does not allow at all.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn yaml_quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+fn yaml_block(out: &mut String, key: &str, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "  {}: |", key);
+    for line in text.lines() {
+        let _ = writeln!(out, "    {}", line);
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(encode_error)
+}
+
+/// `value` as indented JSON, for reports other modules produce.
+pub fn encode_pretty<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).map_err(encode_error)
+}
+
+fn encode_error(e: serde_json::Error) -> CodeRunnerError {
+    CodeRunnerError::ExecutionFailed(format!("Cannot encode report: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::RunRecord;
+
+    fn missing(file: &str) -> FileRun {
+        FileRun {
+            file: file.to_string(),
+            cmd_spec: None,
+            warnings: Vec::new(),
+            record: RunRecord::failed(CodeRunnerError::FileNotFound(file.to_string())),
+        }
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("ndjson".parse::<OutputFormat>().unwrap(), OutputFormat::Ndjson);
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_run_json_includes_error() {
+        let json: serde_json::Value = serde_json::from_str(&run_json(&missing("a.py")).unwrap()).unwrap();
+        assert_eq!(json["file"], "a.py");
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["language"], serde_json::Value::Null);
+        assert_eq!(json["error"]["kind"], "file_not_found");
+    }
+
+    fn passed(file: &str, stdout: &str) -> FileRun {
+        let spec = crate::CommandSpec::new("python3".to_string(), vec![file.to_string()]).with_language("python");
+        let result = crate::executor::ExecutionResult {
+            command: format!("python3 {}", file),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+            signal: None,
+            success: true,
+            truncated: false,
+            wall_time: std::time::Duration::from_millis(250),
+            cpu_time: None,
+            peak_memory_kb: None,
+            termination: crate::executor::Termination::Exited,
+            diagnostics: Vec::new(),
+        };
+        FileRun {
+            file: file.to_string(),
+            cmd_spec: Some(spec),
+            warnings: Vec::new(),
+            record: RunRecord { results: vec![result], error: None },
+        }
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let xml = junit(&[passed("a.py", "1 < 2\n"), missing("b.py")]);
+        assert!(xml.contains("<testsuites tests=\"2\" failures=\"0\" errors=\"1\" time=\"0.250\">"));
+        assert!(xml.contains("<testcase name=\"a.py\" classname=\"python\" time=\"0.250\">"));
+        assert!(xml.contains("<system-out>1 &lt; 2\n</system-out>"));
+        assert!(xml.contains("<error type=\"failed\" message=\"File not found: b.py\"/>"));
+    }
+
+    #[test]
+    fn test_tap_report() {
+        let tap = tap(&[passed("a.py", "hello\n"), missing("b.py")]);
+        let lines: Vec<_> = tap.lines().collect();
+        assert_eq!(lines[..3], ["TAP version 13", "1..2", "ok 1 - a.py"]);
+        assert!(lines.contains(&"    hello"));
+        assert!(lines.contains(&"not ok 2 - b.py"));
+        assert!(lines.contains(&"  message: \"File not found: b.py\""));
+    }
+
+    #[test]
+    fn test_xml_escape_drops_control_characters() {
+        assert_eq!(xml_escape("a\u{1b}[0m&\"b\""), "a[0m&amp;&quot;b&quot;");
+    }
+
+    #[test]
+    fn test_ndjson_lines_are_tagged() {
+        let event = ExecutionEvent::StageStarted { stage: 0, command: "python3 a.py".to_string() };
+        let line: serde_json::Value = serde_json::from_str(&event_line("a.py", &event).unwrap()).unwrap();
+        assert_eq!(line["event"], "stage_started");
+        assert_eq!(line["file"], "a.py");
+
+        let line: serde_json::Value = serde_json::from_str(&summary_line(&[missing("a.py")]).unwrap()).unwrap();
+        assert_eq!(line["event"], "summary");
+        assert_eq!(line["failed"], 1);
+    }
+}
@@ -0,0 +1,145 @@
+//! What each registered language needs installed, and whether it is.
+
+use crate::commands::{self, Language};
+use crate::{utils, Config, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+pub struct LanguageStatus {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub programs: Vec<ProgramStatus>,
+    /// Why the programs couldn't be worked out, such as an empty compiler
+    /// override.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Whether every program was found.
+    pub installed: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProgramStatus {
+    pub name: String,
+    /// Where the program was found, if it was.
+    pub path: Option<PathBuf>,
+}
+
+/// Every registered language, with the programs `config` would run it with.
+pub fn languages(config: &Config) -> Vec<LanguageStatus> {
+    commands::LANGUAGES
+        .iter()
+        .map(|language| {
+            let (programs, error) = match programs(language, config) {
+                Ok(names) => (names, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            };
+            let programs: Vec<ProgramStatus> = programs
+                .into_iter()
+                .map(|name| ProgramStatus { path: utils::find_program(&name), name })
+                .collect();
+            LanguageStatus {
+                name: language.name,
+                extensions: language.extensions,
+                installed: error.is_none()
+                    && !programs.is_empty()
+                    && programs.iter().all(|program| program.path.is_some()),
+                programs,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// The programs, in order, that running a file of `language` needs.
+pub fn programs(language: &Language, config: &Config) -> Result<Vec<String>> {
+    commands::required_programs(language, config)
+}
+
+/// A table of languages, their extensions, runners and whether they're
+/// installed.
+pub fn render(languages: &[LanguageStatus]) -> String {
+    let mut out = format!("{:<12} {:<16} {:<20} {}\n", "LANGUAGE", "EXTENSIONS", "RUNNER", "INSTALLED");
+    for language in languages {
+        let extensions = language.extensions.iter().map(|ext| format!(".{}", ext)).collect::<Vec<_>>();
+        let runners = language.programs.iter().map(|program| program.name.as_str()).collect::<Vec<_>>();
+        let missing = language.programs.iter()
+            .filter(|program| program.path.is_none())
+            .map(|program| program.name.as_str())
+            .collect::<Vec<_>>();
+        let installed = match &language.error {
+            Some(error) => format!("no ({})", error),
+            None if missing.is_empty() => "yes".to_string(),
+            None => format!("no ({} missing)", missing.join(", ")),
+        };
+
+        let _ = writeln!(
+            out,
+            "{:<12} {:<16} {:<20} {}",
+            language.name,
+            extensions.join(" "),
+            runners.join(", "),
+            installed,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LanguageConfig;
+
+    #[test]
+    fn test_programs_for_each_stage() {
+        let config = Config::default();
+        let programs = |name| programs(commands::language_named(name).unwrap(), &config).unwrap();
+
+        assert_eq!(programs("java"), ["javac", "java"]);
+        assert_eq!(programs("c"), ["gcc"]);
+        assert_eq!(programs("typescript"), ["npx", "node"]);
+    }
+
+    #[test]
+    fn test_programs_follow_overrides() {
+        let mut config = Config::default();
+        config.languages.insert(
+            "c".to_string(),
+            LanguageConfig { compiler: Some("clang".to_string()), runner: Some("valgrind -q".to_string()), ..LanguageConfig::default() },
+        );
+
+        assert_eq!(programs(commands::language_named("c").unwrap(), &config).unwrap(), ["clang", "valgrind"]);
+    }
+
+    #[test]
+    fn test_unresolved_programs_are_not_installed() {
+        let mut config = Config::default();
+        config.languages.insert(
+            "c".to_string(),
+            LanguageConfig { compiler: Some(String::new()), ..LanguageConfig::default() },
+        );
+
+        let c = languages(&config).into_iter().find(|language| language.name == "c").unwrap();
+        assert!(!c.installed);
+        assert_eq!(c.error.as_deref(), Some("Configuration error: languages.c.compiler is empty"));
+        assert!(render(&[c]).contains("no (Configuration error: languages.c.compiler is empty)"));
+    }
+
+    #[test]
+    fn test_render_marks_missing_programs() {
+        let languages = [LanguageStatus {
+            name: "java",
+            extensions: &["java"],
+            programs: vec![
+                ProgramStatus { name: "javac".to_string(), path: None },
+                ProgramStatus { name: "java".to_string(), path: Some(PathBuf::from("/usr/bin/java")) },
+            ],
+            error: None,
+            installed: false,
+        }];
+
+        let table = render(&languages);
+        assert!(table.contains("java         .java            javac, java          no (javac missing)"), "{}", table);
+    }
+}
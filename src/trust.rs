@@ -0,0 +1,163 @@
+//! Workspace trust, in the style of direnv: with `require_trust` on, a file
+//! outside `trusted_roots` only runs once approved with `code-runner allow`,
+//! and must be approved again whenever its content changes. The same goes
+//! for `.code-runner.toml` files, which can name any program as a runner.
+
+use crate::{utils, CodeRunnerError, Config, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `~/.local/share/code-runner/allowed.json` on Linux.
+pub fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("code-runner").join("allowed.json"))
+}
+
+/// Approved files, each with the SHA-256 of its content when approved.
+#[derive(Debug, Default, PartialEq)]
+pub struct Allowlist {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl Allowlist {
+    /// Reads the allowlist at `path`; a missing file is an empty list.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let files = serde_json::from_str(&content)
+            .map_err(|e| CodeRunnerError::ConfigError(format!("{}: {}", path.display(), e)))?;
+        Ok(Self { files })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.files)
+            .map_err(|e| CodeRunnerError::ConfigError(e.to_string()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content + "\n")?;
+        Ok(())
+    }
+
+    /// Approves `file` as it is now.
+    pub fn allow(&mut self, file: &Path) -> Result<()> {
+        self.files.insert(file.to_path_buf(), hash(file)?);
+        Ok(())
+    }
+
+    /// Withdraws the approval of `file`, returning whether it had one.
+    pub fn revoke(&mut self, file: &Path) -> bool {
+        self.files.remove(file).is_some()
+    }
+
+    /// Checks that `file` was approved with its current content.
+    pub fn check(&self, file: &Path) -> Result<()> {
+        let problem = match self.files.get(file) {
+            None => "has not been allowed",
+            Some(approved) if *approved != hash(file)? => "changed since it was allowed",
+            Some(_) => return Ok(()),
+        };
+        Err(CodeRunnerError::Untrusted(format!(
+            "{} {}; review it, then run `code-runner allow {}`",
+            file.display(),
+            problem,
+            file.display()
+        )))
+    }
+}
+
+/// Checks that `file`, an absolute path, may run under `config`.
+pub fn check(file: &Path, config: &Config) -> Result<()> {
+    if !config.require_trust || utils::is_under_any(file, &config.trusted_roots) {
+        return Ok(());
+    }
+    let allowlist = match path() {
+        Some(path) => Allowlist::load(&path)?,
+        None => Allowlist::default(),
+    };
+    allowlist.check(file)
+}
+
+/// The SHA-256 of `file`'s content, in hex.
+fn hash(file: &Path) -> Result<String> {
+    let digest = Sha256::digest(fs::read(file)?);
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    Ok(hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_tracks_content() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("script.py");
+        fs::write(&file, "print(1)\n").unwrap();
+
+        let mut allowlist = Allowlist::default();
+        let error = allowlist.check(&file).unwrap_err().to_string();
+        assert!(error.contains("has not been allowed"), "{}", error);
+        assert!(error.ends_with(&format!("`code-runner allow {}`", file.display())), "{}", error);
+
+        allowlist.allow(&file).unwrap();
+        assert!(allowlist.check(&file).is_ok());
+
+        fs::write(&file, "print(2)\n").unwrap();
+        let error = allowlist.check(&file).unwrap_err().to_string();
+        assert!(error.contains("changed since it was allowed"), "{}", error);
+
+        assert!(allowlist.revoke(&file));
+        assert!(!allowlist.revoke(&file));
+    }
+
+    #[test]
+    fn test_allowlist_round_trips() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("main.c");
+        fs::write(&file, "int main(void) { return 0; }\n").unwrap();
+        let path = dir.path().join("data/allowed.json");
+        assert_eq!(Allowlist::load(&path).unwrap(), Allowlist::default());
+
+        let mut allowlist = Allowlist::default();
+        allowlist.allow(&file).unwrap();
+        allowlist.save(&path).unwrap();
+        assert_eq!(Allowlist::load(&path).unwrap(), allowlist);
+
+        fs::write(&path, "not json").unwrap();
+        assert!(Allowlist::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_trusted_roots_and_opt_in() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let file = root.join("script.py");
+        fs::write(&file, "print(1)\n").unwrap();
+
+        let mut config = Config::default();
+        assert!(check(&file, &config).is_ok());
+
+        config.require_trust = true;
+        config.trusted_roots = vec![root.clone()];
+        assert!(check(&file, &config).is_ok());
+        config.trusted_roots = vec![root.join("elsewhere")];
+        assert!(check(&file, &config).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("empty");
+        fs::write(&file, "").unwrap();
+        assert_eq!(hash(&file).unwrap(), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+}
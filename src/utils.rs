@@ -1,9 +1,13 @@
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
 
 pub struct FileInfo {
     pub ext: String,
     pub abs_path: PathBuf,
+    /// The path as given, before symlinks were resolved.
+    pub path: PathBuf,
 }
 
 pub fn get_file_info(file: &str) -> FileInfo {
@@ -20,7 +24,16 @@ pub fn get_file_info(file: &str) -> FileInfo {
         Err(_) => PathBuf::from(file),
     };
     
-    FileInfo { ext, abs_path }
+    FileInfo { ext, abs_path, path: path.to_path_buf() }
+}
+
+/// Whether `path`, an absolute path with symlinks resolved, is inside one of
+/// `roots`.
+pub fn is_under_any(path: &Path, roots: &[PathBuf]) -> bool {
+    roots.iter().any(|root| {
+        let root = fs::canonicalize(root).map(normalize_path).unwrap_or_else(|_| root.clone());
+        path.starts_with(root)
+    })
 }
 
 #[cfg(target_os = "windows")]
@@ -38,33 +51,104 @@ fn normalize_path(path: PathBuf) -> PathBuf {
     path
 }
 
-pub fn check_program_installed(program: &str) -> crate::Result<()> {
-    if program == "cmd" || program == "sh" {
-        return Ok(());
+/// Build directories are named `code-runner-<pid>-<random>` in the temp dir.
+pub const BUILD_DIR_PREFIX: &str = "code-runner-";
+
+/// A private build directory in the temp dir. It is created with mkdtemp, so
+/// no other user can have made it (or a symlink by its name) first, and is
+/// removed once the last command using it is dropped, unless kept.
+#[derive(Debug)]
+pub struct BuildDirGuard {
+    path: PathBuf,
+    dir: Mutex<Option<TempDir>>,
+}
+
+impl BuildDirGuard {
+    pub fn create() -> std::io::Result<Arc<Self>> {
+        let prefix = format!("{}{}-", BUILD_DIR_PREFIX, std::process::id());
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(&prefix);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(std::fs::Permissions::from_mode(0o700));
+        }
+        let dir = builder.tempdir()?;
+        Ok(Arc::new(Self { path: dir.path().to_path_buf(), dir: Mutex::new(Some(dir)) }))
     }
-    
+
+    /// Names a build directory without creating one, for commands that are
+    /// shown but never run.
+    pub fn placeholder() -> Arc<Self> {
+        let path = std::env::temp_dir().join(format!("{}XXXXXX", BUILD_DIR_PREFIX));
+        Arc::new(Self { path, dir: Mutex::new(None) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leaves the directory in place when the guard is dropped.
+    pub fn keep(&self) {
+        if let Some(dir) = self.dir.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = dir.keep();
+        }
+    }
+}
+
+/// Files and directories whose presence marks the root of a workspace.
+pub const WORKSPACE_MARKERS: &[&str] = &[".git", ".hg", ".svn", ".jj"];
+
+/// Walks up from `start` to the nearest directory containing a VCS marker.
+pub fn find_workspace_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| WORKSPACE_MARKERS.iter().any(|marker| dir.join(marker).exists()))
+        .map(Path::to_path_buf)
+}
+
+pub fn check_program_installed(program: &str) -> crate::Result<()> {
     if program.is_empty() {
         return Err(crate::CodeRunnerError::InvalidPath(
             "Program name cannot be empty".to_string()
         ));
     }
     
-    let check_result = if cfg!(target_os = "windows") {
-        std::process::Command::new("where")
-            .arg(program)
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .output()
-    } else {
-        std::process::Command::new("which")
-            .arg(program)
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .output()
-    };
+    match find_program(program) {
+        Some(_) => Ok(()),
+        None => Err(crate::CodeRunnerError::ProgramNotInstalled(program.to_string())),
+    }
+}
+
+/// Where `program` would be started from: itself if it is a path, otherwise
+/// the first match in `PATH`.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return executable(path);
+    }
     
-    match check_result {
-        Ok(output) if output.status.success() => Ok(()),
-        _ => Err(crate::CodeRunnerError::ProgramNotInstalled(program.to_string())),
+    let dirs = std::env::var_os("PATH")?;
+    std::env::split_paths(&dirs).find_map(|dir| executable(&dir.join(program)))
+}
+
+#[cfg(unix)]
+fn executable(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = fs::metadata(path).ok()?;
+    (metadata.is_file() && metadata.permissions().mode() & 0o111 != 0).then(|| path.to_path_buf())
+}
+
+/// Windows programs are found with any of the `PATHEXT` extensions.
+#[cfg(not(unix))]
+fn executable(path: &Path) -> Option<PathBuf> {
+    if path.extension().is_some() && path.is_file() {
+        return Some(path.to_path_buf());
     }
-}
\ No newline at end of file
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    extensions
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| path.with_extension(ext.trim_start_matches('.')))
+        .find(|candidate| candidate.is_file())
+}
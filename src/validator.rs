@@ -1,14 +1,39 @@
-use crate::{CodeRunnerError, Result, FileInfo};
-use std::path::Path;
+use crate::{commands, CodeRunnerError, Result, FileInfo};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::fs;
 
+/// How much of a file is read to tell whether it is text, and how it's encoded.
+const SNIFF_BYTES: u64 = 8192;
+
+/// Languages whose tools, PowerShell and .NET, pick the encoding from a byte
+/// order mark, so UTF-16 files saved by Windows editors run as they are.
+const READS_BYTE_ORDER_MARK: &[&str] = &["powershell", "csharp", "fsharp"];
+
 pub struct Validator {
     max_file_size_mb: u64,
+    follow_symlinks: bool,
+    allowed_roots: Vec<PathBuf>,
 }
 
 impl Validator {
     pub fn new(max_file_size_mb: u64) -> Self {
-        Self { max_file_size_mb }
+        Self { max_file_size_mb, follow_symlinks: true, allowed_roots: Vec::new() }
+    }
+    
+    /// Whether a file given as a symlink may run. Directories on the way to
+    /// it may be symlinks either way; `with_allowed_roots` covers where they
+    /// lead.
+    pub fn with_follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+    
+    /// Refuses files outside `roots` once symlinks are resolved. No roots
+    /// allows any file.
+    pub fn with_allowed_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.allowed_roots = roots;
+        self
     }
     
     pub fn validate(&self, file_path: &str) -> Result<()> {
@@ -41,31 +66,96 @@ impl Validator {
         Ok(())
     }
     
-    pub fn validate_file(&self, file_info: &FileInfo) -> Result<()> {
+    /// Checks that `file_info` names a regular file of a supported type that
+    /// is small enough and looks like source code an interpreter can read.
+    /// Returns warnings about things that may still work, such as a UTF-8
+    /// byte order mark.
+    pub fn validate_file(&self, file_info: &FileInfo) -> Result<Vec<String>> {
         if !file_info.abs_path.exists() {
             return Err(CodeRunnerError::FileNotFound(
                 file_info.abs_path.display().to_string()
             ));
         }
         
+        if !self.follow_symlinks && fs::symlink_metadata(&file_info.path)?.is_symlink() {
+            return Err(CodeRunnerError::InvalidPath(
+                format!("{} is a symlink, and follow_symlinks is off", file_info.path.display())
+            ));
+        }
+        
+        if !self.allowed_roots.is_empty() && !crate::utils::is_under_any(&file_info.abs_path, &self.allowed_roots) {
+            let roots: Vec<String> = self.allowed_roots.iter().map(|root| root.display().to_string()).collect();
+            return Err(CodeRunnerError::InvalidPath(
+                format!("{} is outside the allowed roots ({})", file_info.abs_path.display(), roots.join(", "))
+            ));
+        }
+        
         if file_info.abs_path.is_dir() {
             return Err(CodeRunnerError::IsDirectory(
                 file_info.abs_path.display().to_string()
             ));
         }
         
+        // Reading a FIFO or a device could block forever or never end.
+        let metadata = fs::metadata(&file_info.abs_path)?;
+        if !metadata.is_file() {
+            return Err(CodeRunnerError::InvalidPath(
+                format!("{} is {}, not a regular file", file_info.abs_path.display(), special_file_kind(&metadata))
+            ));
+        }
+        
         if file_info.ext.is_empty() {
             return Err(CodeRunnerError::UnsupportedFileType(String::new()));
         }
         
-        let metadata = fs::metadata(&file_info.abs_path)?;
-        let file_size_mb = metadata.len() / (1024 * 1024);
+        let max_bytes = self.max_file_size_mb.saturating_mul(1024 * 1024);
         
-        if file_size_mb > self.max_file_size_mb {
-            return Err(CodeRunnerError::FileTooLarge(file_size_mb, self.max_file_size_mb));
+        if metadata.len() > max_bytes {
+            return Err(CodeRunnerError::FileTooLarge(metadata.len(), max_bytes));
         }
         
-        Ok(())
+        self.validate_content(file_info)
+    }
+    
+    /// Rejects binary files and wide encodings, unless marked for a language
+    /// that reads the mark, and shell scripts with Windows line endings,
+    /// which bash reads as part of every command.
+    fn validate_content(&self, file_info: &FileInfo) -> Result<Vec<String>> {
+        let path = file_info.abs_path.display();
+        let mut head = Vec::new();
+        fs::File::open(&file_info.abs_path)?.take(SNIFF_BYTES).read_to_end(&mut head)?;
+        
+        let language = commands::find_language(&file_info.ext).map(|language| language.name);
+        let reads_mark = language.is_some_and(|name| READS_BYTE_ORDER_MARK.contains(&name));
+        if reads_mark && wide_byte_order_mark(&head).is_some() {
+            return Ok(Vec::new());
+        }
+        
+        if let Some(encoding) = wide_encoding(&head) {
+            return Err(CodeRunnerError::UnsupportedEncoding(
+                format!("{} is {}; save it as UTF-8", path, encoding)
+            ));
+        }
+        
+        if head.contains(&0) {
+            return Err(CodeRunnerError::BinaryFile(path.to_string()));
+        }
+        
+        if language == Some("shell") && head.windows(2).any(|pair| pair == b"\r\n") {
+            return Err(CodeRunnerError::UnsupportedEncoding(
+                format!("{} has Windows (CRLF) line endings, which break shell scripts; convert it with `dos2unix`", path)
+            ));
+        }
+        
+        let mut warnings = Vec::new();
+        if !reads_mark && head.starts_with(b"\xEF\xBB\xBF") {
+            warnings.push(format!("{} starts with a UTF-8 byte order mark, which some interpreters reject", path));
+        }
+        // A multi-byte character cut off at the end of `head` is fine.
+        if std::str::from_utf8(&head).is_err_and(|e| e.error_len().is_some()) {
+            warnings.push(format!("{} is not valid UTF-8, so non-ASCII text in it may be misread", path));
+        }
+        Ok(warnings)
     }
     
     fn validate_windows_reserved(&self, path: &str) -> Result<()> {
@@ -92,4 +182,55 @@ impl Validator {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// The UTF-16 or UTF-32 encoding named by the byte order mark `head` starts
+/// with, if any.
+fn wide_byte_order_mark(head: &[u8]) -> Option<&'static str> {
+    const MARKS: &[(&[u8], &str)] = &[
+        (b"\xFF\xFE\0\0", "UTF-32LE"),
+        (b"\0\0\xFE\xFF", "UTF-32BE"),
+        (b"\xFF\xFE", "UTF-16LE"),
+        (b"\xFE\xFF", "UTF-16BE"),
+    ];
+    MARKS.iter().find(|(mark, _)| head.starts_with(mark)).map(|(_, encoding)| *encoding)
+}
+
+/// The UTF-16 or UTF-32 encoding `head` is in, by its byte order mark or,
+/// without one, by every other byte being zero as it is for ASCII text.
+fn wide_encoding(head: &[u8]) -> Option<&'static str> {
+    if let Some(encoding) = wide_byte_order_mark(head) {
+        return Some(encoding);
+    }
+    
+    let pairs = head.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let zeros = |offset: usize| head[offset..].iter().step_by(2).take(pairs).filter(|byte| **byte == 0).count();
+    match (zeros(0), zeros(1)) {
+        (0, odd) if odd * 10 >= pairs * 9 => Some("UTF-16LE"),
+        (even, 0) if even * 10 >= pairs * 9 => Some("UTF-16BE"),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn special_file_kind(metadata: &fs::Metadata) -> &'static str {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = metadata.file_type();
+    if file_type.is_fifo() {
+        "a FIFO"
+    } else if file_type.is_socket() {
+        "a socket"
+    } else if file_type.is_char_device() || file_type.is_block_device() {
+        "a device file"
+    } else {
+        "a special file"
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_kind(_metadata: &fs::Metadata) -> &'static str {
+    "a special file"
+}
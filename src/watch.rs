@@ -0,0 +1,158 @@
+use crate::{commands, CodeRunnerError, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const CLEAR_SCREEN: &str = "\x1B[2J\x1B[3J\x1B[H";
+
+pub struct WatchOptions {
+    /// Quiet period that must follow a change before re-running.
+    pub debounce: Duration,
+    /// Re-run when any source file next to the watched one changes.
+    pub watch_dir: bool,
+    pub clear_screen: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(200),
+            watch_dir: false,
+            clear_screen: true,
+        }
+    }
+}
+
+/// Runs `run` and re-runs it whenever `file` changes, until the process is
+/// interrupted. A run still in progress when a change arrives is cancelled
+/// through the flag passed to it.
+///
+/// The parent directory is watched rather than the file itself, because
+/// editors that save atomically replace the file and would drop the watch.
+pub fn watch<F>(file: &Path, options: &WatchOptions, run: F) -> Result<()>
+where
+    F: Fn(Arc<AtomicBool>) -> Result<()> + Send + Sync + 'static,
+{
+    let dir = file.parent().ok_or_else(|| CodeRunnerError::InvalidPath(
+        format!("Cannot watch '{}': no parent directory", file.display())
+    ))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    }).map_err(watch_error)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive).map_err(watch_error)?;
+
+    let run = Arc::new(run);
+    let file = file.to_path_buf();
+
+    loop {
+        if options.clear_screen {
+            print!("{}", CLEAR_SCREEN);
+            io::stdout().flush()?;
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let run = run.clone();
+            let cancel = cancel.clone();
+            let file = file.clone();
+            thread::spawn(move || {
+                match run(cancel) {
+                    Err(CodeRunnerError::Cancelled) => return,
+                    Err(e) => eprintln!("Error: {}", e),
+                    Ok(()) => {}
+                }
+                eprintln!("\n[watching {} for changes]", file.display());
+            })
+        };
+
+        wait_for_change(&rx, &file, options)?;
+
+        cancel.store(true, Ordering::SeqCst);
+        let _ = handle.join();
+    }
+}
+
+fn wait_for_change(rx: &Receiver<notify::Result<Event>>, file: &Path, options: &WatchOptions) -> Result<()> {
+    loop {
+        let event = rx.recv().map_err(|_| watch_closed())?.map_err(watch_error)?;
+        if is_relevant(&event, file, options.watch_dir) {
+            break;
+        }
+    }
+
+    loop {
+        match rx.recv_timeout(options.debounce) {
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => return Err(watch_closed()),
+        }
+    }
+}
+
+fn is_relevant(event: &Event, file: &Path, watch_dir: bool) -> bool {
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+        return false;
+    }
+
+    event.paths.iter().any(|path| {
+        if watch_dir {
+            is_source_file(path)
+        } else {
+            path.file_name() == file.file_name()
+        }
+    })
+}
+
+/// Build artifacts (`main`, `main.jar`, `*.class`) must not retrigger a run.
+fn is_source_file(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    commands::find_language(&ext).is_some()
+}
+
+fn watch_error(e: notify::Error) -> CodeRunnerError {
+    CodeRunnerError::ExecutionFailed(format!("File watcher failed: {}", e))
+}
+
+fn watch_closed() -> CodeRunnerError {
+    CodeRunnerError::ExecutionFailed("File watcher stopped unexpectedly".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind};
+    use std::path::PathBuf;
+
+    fn event(kind: EventKind, path: &str) -> Event {
+        Event::new(kind).add_path(PathBuf::from(path))
+    }
+
+    #[test]
+    fn test_only_watched_file_is_relevant() {
+        let file = Path::new("/src/app.py");
+        let modify = EventKind::Modify(ModifyKind::Any);
+        assert!(is_relevant(&event(modify, "/src/app.py"), file, false));
+        assert!(!is_relevant(&event(modify, "/src/other.py"), file, false));
+        assert!(!is_relevant(&event(EventKind::Access(notify::event::AccessKind::Any), "/src/app.py"), file, false));
+    }
+
+    #[test]
+    fn test_watch_dir_ignores_artifacts() {
+        let file = Path::new("/src/main.c");
+        let create = EventKind::Create(CreateKind::File);
+        assert!(is_relevant(&event(create, "/src/util.h.c"), file, true));
+        assert!(!is_relevant(&event(create, "/src/main"), file, true));
+        assert!(!is_relevant(&event(create, "/src/Main.class"), file, true));
+    }
+}
@@ -1,4 +1,4 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 use std::fs;
 use tempfile::TempDir;
@@ -9,13 +9,21 @@ fn create_test_file(dir: &TempDir, name: &str, content: &str) -> String {
     file_path.to_str().unwrap().to_string()
 }
 
+/// `code-runner` with its user config and data (history, allowed files) kept
+/// in `dir`, away from the real ones on this machine.
+fn code_runner(dir: &TempDir) -> assert_cmd::Command {
+    let mut cmd = cargo_bin_cmd!("code-runner");
+    cmd.env("XDG_CONFIG_HOME", dir.path().join("config"))
+        .env("XDG_DATA_HOME", dir.path().join("data"));
+    cmd
+}
+
 #[test]
 fn test_python_execution() {
     let temp_dir = TempDir::new().unwrap();
     let file = create_test_file(&temp_dir, "test.py", "print('Hello, World!')");
     
-    Command::cargo_bin("code-runner")
-        .unwrap()
+    code_runner(&temp_dir)
         .arg(&file)
         .assert()
         .success()
@@ -27,8 +35,7 @@ fn test_javascript_execution() {
     let temp_dir = TempDir::new().unwrap();
     let file = create_test_file(&temp_dir, "test.js", "console.log('Hello, JS!');");
     
-    Command::cargo_bin("code-runner")
-        .unwrap()
+    code_runner(&temp_dir)
         .arg(&file)
         .assert()
         .success()
@@ -40,8 +47,7 @@ fn test_file_with_spaces() {
     let temp_dir = TempDir::new().unwrap();
     let file = create_test_file(&temp_dir, "hello world.py", "print('Spaces work!')");
     
-    Command::cargo_bin("code-runner")
-        .unwrap()
+    code_runner(&temp_dir)
         .arg(&file)
         .assert()
         .success()
@@ -50,8 +56,8 @@ fn test_file_with_spaces() {
 
 #[test]
 fn test_nonexistent_file() {
-    Command::cargo_bin("code-runner")
-        .unwrap()
+    let temp_dir = TempDir::new().unwrap();
+    code_runner(&temp_dir)
         .arg("nonexistent.py")
         .assert()
         .failure()
@@ -63,8 +69,7 @@ fn test_unsupported_extension() {
     let temp_dir = TempDir::new().unwrap();
     let file = create_test_file(&temp_dir, "test.xyz", "some content");
     
-    Command::cargo_bin("code-runner")
-        .unwrap()
+    code_runner(&temp_dir)
         .arg(&file)
         .assert()
         .failure()
@@ -73,9 +78,487 @@ fn test_unsupported_extension() {
 
 #[test]
 fn test_no_arguments() {
-    Command::cargo_bin("code-runner")
-        .unwrap()
+    let temp_dir = TempDir::new().unwrap();
+    code_runner(&temp_dir)
         .assert()
         .failure()
         .stderr(predicate::str::contains("Usage"));
 }
+
+#[test]
+fn test_cwd_file_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_file(&temp_dir, "data.txt", "from data file");
+    let file = create_test_file(&temp_dir, "read.py", "print(open('data.txt').read())");
+    
+    code_runner(&temp_dir)
+        .args(["--cwd", "file_dir"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("from data file"));
+}
+
+#[test]
+fn test_batch_run_with_summary() {
+    let temp_dir = TempDir::new().unwrap();
+    let ok = create_test_file(&temp_dir, "ok.py", "print('batch ok')");
+    let bad = create_test_file(&temp_dir, "bad.py", "raise SystemExit(3)");
+    
+    code_runner(&temp_dir)
+        .args([&ok, &bad, "-j", "2"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("batch ok"))
+        .stdout(predicate::str::contains("2 files: 1 succeeded, 1 failed"));
+}
+
+#[test]
+fn test_batch_uses_each_files_project_config() {
+    let temp_dir = TempDir::new().unwrap();
+    for project in ["one", "two"] {
+        let dir = temp_dir.path().join(project);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join(".code-runner.toml"), format!("[env]\nPROJECT = \"{}\"\n", project)).unwrap();
+        fs::write(dir.join("show.py"), "import os\nprint('project', os.environ['PROJECT'])\n").unwrap();
+    }
+
+    code_runner(&temp_dir)
+        .current_dir(temp_dir.path())
+        .args(["one/show.py", "two/show.py"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("project one"))
+        .stdout(predicate::str::contains("project two"));
+}
+
+#[test]
+fn test_json_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "hello.py", "print('json out')");
+    
+    let output = code_runner(&temp_dir)
+        .args(["--format", "json", &file])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["language"], "python");
+    assert_eq!(json["status"], "passed");
+    assert_eq!(json["stages"][0]["stdout"], "json out\n");
+    assert_eq!(json["stages"][0]["exit_code"], 0);
+}
+
+#[test]
+fn test_ndjson_format_streams_events() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "bad.py", "raise SystemExit(4)");
+    
+    let output = code_runner(&temp_dir)
+        .args(["--format", "ndjson", &file])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.first().unwrap()["event"], "run_started");
+    let last = events.last().unwrap();
+    assert_eq!(last["event"], "run_finished");
+    assert_eq!(last["stages"][0]["exit_code"], 4);
+    assert_eq!(last["error"]["kind"], "execution_failed");
+}
+
+#[test]
+fn test_tap_format_for_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let ok = create_test_file(&temp_dir, "ok.py", "print('tap ok')");
+    let bad = create_test_file(&temp_dir, "bad.py", "raise SystemExit(2)");
+    
+    code_runner(&temp_dir)
+        .args(["--format", "tap", "-j", "1", &ok, &bad])
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with("TAP version 13\n1..2\n"))
+        .stdout(predicate::str::contains("\n    tap ok\n"))
+        .stdout(predicate::str::contains("not ok 2 - "));
+}
+
+#[test]
+fn test_python_traceback_diagnostics() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "boom.py", "x = 1\nraise ValueError('boom')\n");
+    
+    let output = code_runner(&temp_dir)
+        .args(["--format", "json", &file])
+        .output()
+        .unwrap();
+    
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostic = &json["stages"][0]["diagnostics"][0];
+    assert!(diagnostic["file"].as_str().unwrap().ends_with("boom.py"));
+    assert_eq!(diagnostic["line"], 2);
+    assert_eq!(diagnostic["severity"], "error");
+    assert_eq!(diagnostic["message"], "ValueError: boom");
+}
+
+#[test]
+fn test_project_config_and_env_layers() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join(".code-runner.toml"), "silent_mode = true\ntimeout = 7\n").unwrap();
+    let file = create_test_file(&temp_dir, "quiet.py", "print('layered')");
+    
+    // silent_mode from the project file hides the "Running:" banner and output.
+    code_runner(&temp_dir)
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout("");
+    
+    code_runner(&temp_dir)
+        .args(["config", "show", "--origin", &file])
+        .env("CODE_RUNNER_TIMEOUT", "3")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("silent_mode = true"))
+        .stdout(predicate::str::contains(".code-runner.toml"))
+        .stdout(predicate::str::is_match(r"timeout = 3 +# environment CODE_RUNNER_TIMEOUT").unwrap());
+}
+
+#[test]
+fn test_malformed_config_is_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "app.py", "print('never')");
+    
+    fs::write(temp_dir.path().join(".code-runner.toml"), "timout = 5\n").unwrap();
+    code_runner(&temp_dir)
+        .arg(&file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("unknown key 'timout', did you mean 'timeout'?"));
+    
+    fs::write(temp_dir.path().join(".code-runner.toml"), "timeout = [\n").unwrap();
+    code_runner(&temp_dir)
+        .arg(&file)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("never").not())
+        .stderr(predicate::str::contains(".code-runner.toml:2:1: invalid array, expected `]`"));
+}
+
+#[test]
+fn test_config_set_get_unset() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = |args: &[&str]| {
+        let mut cmd = code_runner(&temp_dir);
+        cmd.arg("config").args(args).arg("--project").current_dir(temp_dir.path());
+        cmd
+    };
+    
+    config(&["init"]).assert().success();
+    let project_file = temp_dir.path().join(".code-runner.toml");
+    assert!(fs::read_to_string(&project_file).unwrap().starts_with("# Code Runner Configuration"));
+    config(&["init"]).assert().failure().stderr(predicate::str::contains("--force"));
+    
+    config(&["set", "timeout", "12"]).assert().success();
+    config(&["set", "timout", "12"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean 'timeout'"));
+    config(&["get", "timeout"]).assert().success().stdout("12\n");
+    
+    let content = fs::read_to_string(&project_file).unwrap();
+    assert!(content.contains("# Execution timeout in seconds\ntimeout = 12\n"));
+    
+    config(&["unset", "timeout"]).assert().success();
+    config(&["get", "timeout"]).assert().success().stdout("30\n");
+    config(&["path"]).assert().success().stdout(predicate::str::contains(".code-runner.toml"));
+}
+
+#[test]
+fn test_unknown_profile_is_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.py");
+    fs::write(&file_path, "print('hi')").unwrap();
+
+    let mut cmd = code_runner(&temp_dir);
+    cmd.arg("--profile").arg("fast").arg(&file_path);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile 'fast'"));
+}
+
+#[test]
+fn test_magic_comment_directives() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("echo.py");
+    fs::write(temp_dir.path().join("in.txt"), "from stdin\n").unwrap();
+    fs::write(
+        &file_path,
+        "# code-runner: args=\"--n 5\" stdin=in.txt timeout=5\nimport sys\nprint(sys.argv[1:], sys.stdin.read().strip())\n",
+    ).unwrap();
+
+    let mut cmd = code_runner(&temp_dir);
+    cmd.arg(&file_path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("['--n', '5'] from stdin"));
+
+    fs::write(&file_path, "# code-runner: stdin=missing.txt\nprint('hi')\n").unwrap();
+    let mut cmd = code_runner(&temp_dir);
+    cmd.arg(&file_path);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.txt"));
+}
+
+#[test]
+fn test_run_subcommand_with_overrides() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "script.txt", "import time\ntime.sleep(5)\n");
+
+    code_runner(&temp_dir)
+        .args(["run", "--lang", "python", "--timeout", "1", &file])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("exceeded 1 seconds"));
+
+    code_runner(&temp_dir)
+        .args(["--timeout", "18446744073709551615", &file])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--timeout 18446744073709551615 is too large"));
+
+    code_runner(&temp_dir)
+        .args(["--lang", "cobol", &file])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown language 'cobol'"));
+}
+
+#[test]
+fn test_timeout_flag_overrides_the_directive() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "slow.py", "# code-runner: timeout=60\nimport time\ntime.sleep(5)\n");
+
+    code_runner(&temp_dir)
+        .args(["--timeout", "1", &file])
+        .timeout(std::time::Duration::from_secs(4))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("exceeded 1 seconds"));
+
+    code_runner(&temp_dir)
+        .args(["--explain", "--timeout", "1", &file])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Timeout    1s per stage\n           # timeout is set by command line --timeout"));
+}
+
+#[test]
+fn test_dry_run_and_explain() {
+    let temp_dir = TempDir::new().unwrap();
+    let marker = temp_dir.path().join("ran");
+    let file = create_test_file(
+        &temp_dir,
+        "touch.py",
+        &format!("open({:?}, 'w').close()\n", marker.to_str().unwrap()),
+    );
+
+    code_runner(&temp_dir)
+        .args(["--dry-run", &file])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Language   python"))
+        .stdout(predicate::str::contains("Stage 1    python"))
+        .stdout(predicate::str::contains("# ").not());
+    assert!(!marker.exists());
+
+    code_runner(&temp_dir)
+        .args(["--explain", "--timeout", "7", &file])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Timeout    7s per stage\n           # timeout is set by command line --timeout"));
+    assert!(!marker.exists());
+}
+
+#[test]
+fn test_untrusted_files_need_approval() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "pulled.py", "print('ran')");
+    let code_runner = || {
+        let mut cmd = code_runner(&temp_dir);
+        cmd.env("CODE_RUNNER_REQUIRE_TRUST", "true");
+        cmd
+    };
+
+    code_runner()
+        .arg(&file)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("ran").not())
+        .stderr(predicate::str::contains("has not been allowed"));
+
+    code_runner().args(["allow", &file]).assert().success();
+    code_runner().arg(&file).assert().success().stdout(predicate::str::contains("ran"));
+
+    fs::write(&file, "print('changed')").unwrap();
+    code_runner()
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("changed since it was allowed"));
+}
+
+#[test]
+fn test_edited_files_rerun_by_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "edited.py", "print('first')");
+    code_runner(&temp_dir).arg(&file).assert().success().stdout(predicate::str::contains("first"));
+
+    fs::write(&file, "print('second')").unwrap();
+    code_runner(&temp_dir).arg(&file).assert().success().stdout(predicate::str::contains("second"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sigterm_stops_the_program() {
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let pid_file = temp_dir.path().join("pid");
+    let file = create_test_file(
+        &temp_dir,
+        "loop.py",
+        &format!(
+            "import os, time\nopen({:?}, 'w').write(str(os.getpid()))\nwhile True: time.sleep(0.1)\n",
+            pid_file.display().to_string()
+        ),
+    );
+
+    let mut runner = Command::new(assert_cmd::cargo::cargo_bin!("code-runner"))
+        .arg(&file)
+        .env("XDG_CONFIG_HOME", temp_dir.path().join("config"))
+        .env("XDG_DATA_HOME", temp_dir.path().join("data"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    let pid = loop {
+        if let Some(pid) = fs::read_to_string(&pid_file).ok().filter(|pid| !pid.is_empty()) {
+            break pid;
+        }
+        assert!(started.elapsed() < Duration::from_secs(10), "program never started");
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    Command::new("kill").args(["-TERM", &runner.id().to_string()]).status().unwrap();
+    runner.wait().unwrap();
+
+    // A killed program may linger as a zombie until it is reaped.
+    let running = || {
+        fs::read_to_string(format!("/proc/{}/stat", pid))
+            .is_ok_and(|stat| stat.rsplit(") ").next().is_some_and(|rest| !rest.starts_with('Z')))
+    };
+    let stopped = Instant::now();
+    while running() && stopped.elapsed() < Duration::from_secs(5) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(!running(), "program {} outlived the runner", pid);
+}
+
+#[test]
+fn test_history_records_runs() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "hello.py", "print('hello')");
+
+    code_runner(&temp_dir)
+        .args(["--silent", &file])
+        .assert()
+        .success()
+        .stdout("");
+
+    code_runner(&temp_dir)
+        .arg("history")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ok").and(predicate::str::contains("hello.py")));
+
+    code_runner(&temp_dir)
+        .args(["history", "clear"])
+        .assert()
+        .success();
+    assert!(!temp_dir.path().join("data/code-runner/history.jsonl").exists());
+}
+
+#[test]
+fn test_list_languages_as_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let output = code_runner(&temp_dir).args(["list", "--json"]).output().unwrap();
+    assert!(output.status.success());
+
+    let languages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let java = languages.as_array().unwrap().iter().find(|language| language["name"] == "java").unwrap();
+    assert_eq!(java["extensions"], serde_json::json!(["java"]));
+    assert_eq!(java["programs"][0]["name"], "javac");
+    assert!(java["installed"].is_boolean());
+}
+
+#[test]
+fn test_watch_needs_a_single_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let first = create_test_file(&temp_dir, "first.py", "print(1)");
+    let second = create_test_file(&temp_dir, "second.py", "print(2)");
+
+    code_runner(&temp_dir)
+        .args(["--watch", &first, &second])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--watch and --watch-dir take a single file"));
+
+    code_runner(&temp_dir)
+        .args(["--watch-dir", "-j", "2", &first])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_doctor_reports_broken_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_dir = temp_dir.path().join("config/code-runner");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.toml"), "timeout = \"soon\"\n").unwrap();
+
+    code_runner(&temp_dir)
+        .current_dir(temp_dir.path())
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Toolchains"))
+        .stdout(predicate::str::contains("config.toml:1:11"))
+        .stdout(predicate::str::contains("ok   history directory"))
+        .stderr(predicate::str::contains("Error: doctor found 1 problem(s)"))
+        .stderr(predicate::str::contains("Configuration error").not());
+}
+
+#[test]
+fn test_missing_compiler_is_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(&temp_dir, "main.c", "int main(void) { return 0; }\n");
+    fs::write(
+        temp_dir.path().join(".code-runner.toml"),
+        "[languages.c]\ncompiler = \"surely-not-a-c-compiler\"\n",
+    ).unwrap();
+
+    code_runner(&temp_dir)
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Program not installed: surely-not-a-c-compiler"));
+}